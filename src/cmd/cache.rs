@@ -0,0 +1,57 @@
+use crate::util::{cache, format};
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use comfy_table::Table;
+use std::time::{Duration, SystemTime};
+use tracing::info;
+
+#[derive(Subcommand, Debug, Clone)]
+pub(crate) enum CacheCommands {
+    /// List cached objects
+    Ls,
+    /// Remove cached objects
+    Prune {
+        /// Only remove objects fetched before this duration ago e.g. 7d
+        #[arg(long, value_parser = humantime::parse_duration)]
+        older_than: Option<Duration>,
+    },
+}
+
+pub(crate) async fn run(command: CacheCommands) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        CacheCommands::Ls => {
+            let entries = cache::list()?;
+
+            let mut table = Table::new();
+            let to_string = |time: SystemTime| DateTime::<Utc>::from(time).naive_utc().to_string();
+            table.set_header(vec!["object", "size", "last modified", "fetched at"]);
+            for entry in entries.iter() {
+                table.add_row(vec![
+                    entry.uri.clone(),
+                    format::bytes(entry.size),
+                    to_string(entry.last_modified),
+                    to_string(entry.fetched_at),
+                ]);
+            }
+            println!("{}", table);
+
+            info!(
+                "{} objects, {} in {:?}",
+                entries.len(),
+                format::bytes(entries.iter().map(|entry| entry.size).sum()),
+                cache::get_cache_dir().unwrap_or_default()
+            );
+        }
+        CacheCommands::Prune { older_than } => {
+            let removed = cache::prune(older_than)?;
+
+            info!(
+                "removed {} objects, {}",
+                removed.len(),
+                format::bytes(removed.iter().map(|entry| entry.size).sum())
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use crate::pb::db::operation_client::OperationClient;
+use crate::pb::db::DescribeTableRequest;
+use crate::util::uds::create_channel;
+use comfy_table::Table;
+use tonic::Request;
+
+pub(crate) async fn run(table_name: String) -> Result<(), Box<dyn std::error::Error>> {
+    let channel = create_channel().await?;
+    let mut client = OperationClient::new(channel);
+
+    let resp = client
+        .describe_table(Request::new(DescribeTableRequest { table_name }))
+        .await
+        .map_err(|e| e.message().to_string())?
+        .into_inner();
+
+    let mut table = Table::new();
+    table.set_header(vec![
+        "column",
+        "type",
+        "null %",
+        "min",
+        "max",
+        "description",
+    ]);
+    for column in resp.columns {
+        table.add_row(vec![
+            column.column_name,
+            column.column_type,
+            column.null_percentage,
+            column.min,
+            column.max,
+            column.description,
+        ]);
+    }
+
+    println!("{}", table);
+
+    Ok(())
+}
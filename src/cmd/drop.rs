@@ -0,0 +1,25 @@
+use crate::pb::db::operation_client::OperationClient;
+use crate::pb::db::DropTablesRequest;
+use crate::util::uds::create_channel;
+use tonic::Request;
+use tracing::info;
+
+pub(crate) async fn run(
+    table_names: Vec<String>,
+    all: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let channel = create_channel().await?;
+    let mut client = OperationClient::new(channel);
+
+    let resp = client
+        .drop_tables(Request::new(DropTablesRequest { table_names, all }))
+        .await
+        .map_err(|e| e.message().to_string())?
+        .into_inner();
+
+    for table_name in resp.table_names {
+        info!("dropped {}", table_name);
+    }
+
+    Ok(())
+}
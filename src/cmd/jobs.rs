@@ -0,0 +1,169 @@
+use crate::cmd::load::{progress_bar, update_progress};
+use crate::pb::db::operation_client::OperationClient;
+use crate::pb::db::{CancelJobRequest, GetJobRequest, JobInfo, ListJobsRequest};
+use crate::util::format;
+use crate::util::uds::create_channel;
+use clap::Subcommand;
+use comfy_table::Table;
+use std::time::Duration;
+use tonic::transport::Channel;
+use tonic::Request;
+use tracing::info;
+
+const WAIT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Subcommand, Debug, Clone)]
+pub(crate) enum JobsCommands {
+    /// List background jobs
+    Ls,
+    /// Show the state and progress of a background job
+    Status { job_id: u64 },
+    /// Stop a background job, or forget a finished one
+    Cancel { job_id: u64 },
+    /// Wait until a background load finishes
+    Wait { job_id: u64 },
+}
+
+fn progress(job: &JobInfo) -> String {
+    match job.progress {
+        Some(progress) => format!(
+            "{}/{} objects, {}/{}, {} rows",
+            progress.objects_read,
+            progress.object_count,
+            format::bytes(progress.bytes_read),
+            format::bytes(progress.total_bytes),
+            progress.row_count
+        ),
+        None => String::new(),
+    }
+}
+
+async fn get_job(
+    client: &mut OperationClient<Channel>,
+    job_id: u64,
+) -> Result<JobInfo, Box<dyn std::error::Error>> {
+    Ok(client
+        .get_job(Request::new(GetJobRequest { job_id }))
+        .await
+        .map_err(|e| e.message().to_string())?
+        .into_inner())
+}
+
+pub(crate) async fn run(command: JobsCommands) -> Result<(), Box<dyn std::error::Error>> {
+    let channel = create_channel().await?;
+    let mut client = OperationClient::new(channel);
+
+    match command {
+        JobsCommands::Ls => {
+            let resp = client
+                .list_jobs(Request::new(ListJobsRequest::default()))
+                .await
+                .map_err(|e| e.message().to_string())?
+                .into_inner();
+
+            let mut table = Table::new();
+            table.set_header(vec![
+                "id",
+                "kind",
+                "table",
+                "state",
+                "interval",
+                "started at",
+                "runs",
+                "rows",
+                "last run",
+                "last error",
+            ]);
+            for job in resp.jobs {
+                table.add_row(vec![
+                    job.job_id.to_string(),
+                    job.kind,
+                    job.table_name,
+                    job.state,
+                    if job.interval_secs > 0 {
+                        humantime::format_duration(Duration::from_secs(job.interval_secs))
+                            .to_string()
+                    } else {
+                        String::new()
+                    },
+                    job.started_at,
+                    job.runs.to_string(),
+                    job.row_count.to_string(),
+                    job.last_run,
+                    job.last_error,
+                ]);
+            }
+            println!("{}", table);
+        }
+        JobsCommands::Status { job_id } => {
+            let job = get_job(&mut client, job_id).await?;
+
+            let mut table = Table::new();
+            table.set_header(vec![
+                "id",
+                "kind",
+                "table",
+                "state",
+                "started at",
+                "progress",
+                "rows",
+                "last run",
+                "last error",
+            ]);
+            table.add_row(vec![
+                job.job_id.to_string(),
+                job.kind.clone(),
+                job.table_name.clone(),
+                job.state.clone(),
+                job.started_at.clone(),
+                progress(&job),
+                job.row_count.to_string(),
+                job.last_run.clone(),
+                job.last_error.clone(),
+            ]);
+            println!("{}", table);
+        }
+        JobsCommands::Cancel { job_id } => {
+            client
+                .cancel_job(Request::new(CancelJobRequest { job_id }))
+                .await
+                .map_err(|e| e.message().to_string())?;
+            info!("cancelled job {}", job_id);
+        }
+        JobsCommands::Wait { job_id } => {
+            let bar = progress_bar()?;
+            let job = loop {
+                let job = get_job(&mut client, job_id)
+                    .await
+                    .inspect_err(|_| bar.abandon())?;
+                if job.interval_secs > 0 {
+                    bar.finish_and_clear();
+                    return Err(format!(
+                        "job {} follows {} and does not finish",
+                        job_id, job.table_name
+                    )
+                    .into());
+                }
+                if let Some(progress) = job.progress.as_ref() {
+                    update_progress(&bar, progress);
+                }
+                if job.state != "running" {
+                    break job;
+                }
+
+                tokio::time::sleep(WAIT_INTERVAL).await;
+            };
+            bar.finish_and_clear();
+
+            if !job.last_error.is_empty() {
+                return Err(format!("job {} failed: {}", job_id, job.last_error).into());
+            }
+            info!(
+                "job {} loaded {} rows into {}",
+                job_id, job.row_count, job.table_name
+            );
+        }
+    }
+
+    Ok(())
+}
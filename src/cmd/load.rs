@@ -1,11 +1,119 @@
-use crate::pb::db::management_client::ManagementClient;
+use crate::cmd::server;
+use crate::pb::db::create_table_progress::Event;
+use crate::pb::db::create_table_stream_request::Payload;
 use crate::pb::db::operation_client::OperationClient;
-use crate::pb::db::{CreateTableRequest, HealthCheckRequest};
-use crate::util::uds::{create_channel, get_sock_path};
-use clap::ValueEnum;
-use tokio::process::Command;
+use crate::pb::db::{
+    AzureCredential, CreateTableReply, CreateTableRequest, CreateTableStreamRequest, GcsCredential,
+    LoadProgress, SampleOptions, StartFollowRequest,
+};
+use crate::repo::rejects_table_name;
+use crate::util::{config, format};
+use clap::{Args, ValueEnum};
+use comfy_table::Table;
+use indicatif::style::TemplateError;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
 use tonic::Request;
-use tracing::{debug, info};
+use tracing::{info, warn};
+
+#[derive(Args, Debug, Clone)]
+pub(crate) struct LoadArgs {
+    #[arg(long, value_enum)]
+    table_type: TableType,
+    /// e.g. s3://bucket-name/path/to/**/*.log.gz, gs://..., az://..., https://... (can be repeated)
+    #[arg(long, alias = "s3-uri", required_unless_present_any = ["uri_file", "stdin"])]
+    uri: Vec<String>,
+    /// File containing uris, one per line
+    #[arg(long, alias = "s3-uri-file")]
+    uri_file: Option<PathBuf>,
+    /// [default table name: alb: alb_logs, s3: s3_logs]
+    #[arg(long)]
+    table_name: Option<String>,
+    /// AWS account IDs to load, or `*` for all (alb only) e.g. 123456789012,210987654321
+    #[arg(long, value_delimiter = ',')]
+    accounts: Vec<String>,
+    /// AWS regions to load, or `*` for all (alb only) e.g. ap-northeast-1,us-east-1
+    #[arg(long, value_delimiter = ',')]
+    regions: Vec<String>,
+    /// List the matching objects without creating a table
+    #[arg(long)]
+    dry_run: bool,
+    /// Load even if the matching objects exceed the configured limits
+    #[arg(long)]
+    force: bool,
+    /// Fetch objects through the local cache (see `lupe cache`), which fetches an object
+    /// again once its size or last modified time changes
+    #[arg(long)]
+    cache: bool,
+    /// Insert into the existing table, skipping objects it has already loaded
+    #[arg(long)]
+    append: bool,
+    /// Add source_file and line_number columns, and lb_name (alb) or source_bucket (s3) taken from the object path
+    #[arg(long)]
+    provenance: bool,
+    /// Keep appending newly delivered objects in the background after loading (see `lupe jobs`)
+    #[arg(long, conflicts_with_all = ["stdin", "dry_run"])]
+    follow: bool,
+    /// Load in the background and return its job ID right away (see `lupe jobs`)
+    #[arg(long, conflicts_with_all = ["stdin", "dry_run", "follow"])]
+    detach: bool,
+    /// How often --follow checks for new objects
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "60s", requires = "follow")]
+    interval: Duration,
+    /// Write the rows as Parquet partitioned by date and hour, and query them through a view e.g. parquet:./alb_logs
+    #[arg(long, value_parser = parse_materialize, conflicts_with_all = ["stdin", "provenance"])]
+    materialize: Option<PathBuf>,
+    /// Load a sample of the matching logs, a percentage e.g. 10% or a number of rows (with --sample-by rows)
+    #[arg(long, value_parser = parse_sample, conflicts_with_all = ["stdin", "follow"])]
+    sample: Option<SampleSize>,
+    /// Sample whole objects, or rows of all the objects
+    #[arg(long, value_enum, default_value = "objects", requires = "sample")]
+    sample_by: SampleBy,
+    /// Add a sample_weight column of the number of rows each loaded row stands for
+    #[arg(long, requires = "sample")]
+    sample_weight: bool,
+    /// DuckDB file for the server to open when this load starts it, instead of an in-memory database
+    #[arg(long)]
+    db_path: Option<PathBuf>,
+    /// Pay for the requests to requester pays s3 buckets
+    #[arg(long)]
+    requester_pays: bool,
+    /// HMAC key ID for gs:// uris
+    #[arg(long, env = "LUPE_GCS_KEY_ID", requires = "gcs_secret")]
+    gcs_key_id: Option<String>,
+    /// HMAC secret for gs:// uris
+    #[arg(
+        long,
+        env = "LUPE_GCS_SECRET",
+        hide_env_values = true,
+        requires = "gcs_key_id"
+    )]
+    gcs_secret: Option<String>,
+    /// GCS endpoint e.g. http://localhost:4443 [default: storage.googleapis.com]
+    #[arg(long, env = "LUPE_GCS_ENDPOINT", requires = "gcs_key_id")]
+    gcs_endpoint: Option<String>,
+    /// Azure storage connection string for az:// uris
+    #[arg(
+        long,
+        env = "LUPE_AZURE_CONNECTION_STRING",
+        hide_env_values = true,
+        conflicts_with = "azure_account_name"
+    )]
+    azure_connection_string: Option<String>,
+    /// Azure storage account name for az:// uris, authenticated with the Azure credential chain
+    #[arg(long, env = "LUPE_AZURE_ACCOUNT_NAME")]
+    azure_account_name: Option<String>,
+    /// Read logs from stdin instead of uris (e.g. aws s3 cp s3://bucket/key.log.gz - | zcat | lupe load ...)
+    #[arg(
+        long,
+        conflicts_with_all = ["uri", "uri_file", "accounts", "regions", "dry_run", "cache"]
+    )]
+    stdin: bool,
+}
 
 #[derive(ValueEnum, Debug, Clone)]
 pub(crate) enum TableType {
@@ -22,48 +130,325 @@ impl From<TableType> for i32 {
     }
 }
 
-pub(crate) async fn run(
-    table_type: TableType,
-    table_name: Option<String>,
-    s3_uri: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let exe_path = std::env::current_exe()?;
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SampleSize {
+    Percent(f64),
+    Rows(u64),
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub(crate) enum SampleBy {
+    Objects,
+    Rows,
+}
 
-    if !get_sock_path().exists() {
-        info!("starting server...");
-        let _child = Command::new(exe_path).arg("server").spawn()?;
-        info!("server start successfully");
+impl From<SampleBy> for i32 {
+    fn from(sample_by: SampleBy) -> i32 {
+        match sample_by {
+            SampleBy::Objects => 0,
+            SampleBy::Rows => 1,
+        }
     }
+}
+
+const STDIN_CHUNK_SIZE: usize = 64 * 1024;
+const STDIN_CHANNEL_SIZE: usize = 16;
 
-    let channel = create_channel().await?;
-    let mut mgmt_client = ManagementClient::new(channel.clone());
+fn parse_materialize(value: &str) -> Result<PathBuf, String> {
+    let Some(dir) = value.strip_prefix("parquet:").filter(|dir| !dir.is_empty()) else {
+        return Err("expected parquet:<dir>".into());
+    };
 
-    let health_req = Request::new(HealthCheckRequest::default());
-    match mgmt_client.health_check(health_req).await {
-        Ok(resp) => {
-            debug!("response={:?}", resp);
-            debug!("server is up and running");
+    std::path::absolute(dir).map_err(|e| e.to_string())
+}
+
+fn parse_sample(value: &str) -> Result<SampleSize, String> {
+    match value.strip_suffix('%') {
+        Some(percent) => percent
+            .parse()
+            .map(SampleSize::Percent)
+            .map_err(|_| "expected a percentage e.g. 10%".into()),
+        None => value
+            .parse()
+            .map(SampleSize::Rows)
+            .map_err(|_| "expected a percentage e.g. 10%, or a number of rows".into()),
+    }
+}
+
+fn read_uri_file(path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+    Ok(content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
+async fn send_stdin(tx: mpsc::Sender<CreateTableStreamRequest>) -> std::io::Result<()> {
+    let mut stdin = tokio::io::stdin();
+    loop {
+        let mut buf = vec![0; STDIN_CHUNK_SIZE];
+        let n = stdin.read(&mut buf).await?;
+        if n == 0 {
+            break;
         }
-        Err(e) => {
-            return Err(e.message().into());
+        buf.truncate(n);
+
+        let req = CreateTableStreamRequest {
+            payload: Some(Payload::Chunk(buf)),
+        };
+        if tx.send(req).await.is_err() {
+            // the server has closed the stream, its reply tells why
+            break;
         }
     }
 
-    let table_name = table_name.unwrap_or_else(|| match table_type {
+    Ok(())
+}
+
+pub(crate) fn progress_bar() -> Result<ProgressBar, TemplateError> {
+    Ok(ProgressBar::new(0).with_style(ProgressStyle::with_template(
+        "{spinner} [{elapsed_precise}] {wide_bar} {msg}",
+    )?))
+}
+
+pub(crate) fn update_progress(bar: &ProgressBar, progress: &LoadProgress) {
+    if progress.total_bytes > 0 {
+        bar.set_length(progress.total_bytes);
+        bar.set_position(progress.bytes_read);
+    } else {
+        bar.set_length(progress.object_count);
+        bar.set_position(progress.objects_read);
+    }
+
+    let mut message = format!(
+        "{}/{} objects, {}/{}, {} rows",
+        progress.objects_read,
+        progress.object_count,
+        format::bytes(progress.bytes_read),
+        format::bytes(progress.total_bytes),
+        progress.row_count
+    );
+    if progress.objects_fetched > progress.objects_read {
+        message.push_str(&format!(", {} fetched", progress.objects_fetched));
+    }
+    if progress.objects_read > 0 && progress.objects_read < progress.object_count {
+        message.push_str(&format!(
+            ", ETA {}",
+            humantime::format_duration(Duration::from_secs(progress.eta_ms.div_ceil(1000)))
+        ));
+    }
+    bar.set_message(message);
+}
+
+fn print_summary(resp: &CreateTableReply) {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "rows",
+        "objects",
+        "size",
+        "duration",
+        "first time",
+        "last time",
+        "rejects",
+    ]);
+    table.add_row(vec![
+        resp.row_count.to_string(),
+        resp.object_count.to_string(),
+        format::bytes(resp.byte_count),
+        humantime::format_duration(Duration::from_millis(resp.duration_ms)).to_string(),
+        resp.first_time.clone(),
+        resp.last_time.clone(),
+        resp.reject_count.to_string(),
+    ]);
+    println!("{}", table);
+}
+
+fn warn_rejects(table_name: &str, reject_count: u64) {
+    if reject_count > 0 {
+        warn!(
+            "{} lines could not be parsed, see {}",
+            reject_count,
+            rejects_table_name(table_name)
+        );
+    }
+}
+
+pub(crate) async fn run(args: LoadArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::load()?;
+
+    let mut uris = args.uri;
+    if let Some(path) = args.uri_file {
+        uris.extend(read_uri_file(&path)?);
+    }
+    if uris.is_empty() && !args.stdin {
+        return Err("no uri is specified".into());
+    }
+
+    let channel = server::start(args.db_path.as_deref()).await?;
+
+    let table_type = args.table_type;
+    let table_name = args.table_name.unwrap_or_else(|| match table_type {
         TableType::Alb => "alb_logs".to_string(),
         TableType::S3 => "s3_logs".to_string(),
     });
 
     let mut ope_client = OperationClient::new(channel);
-    let create_table_req = Request::new(CreateTableRequest {
+    let create_table_req = CreateTableRequest {
         table_type: table_type.into(),
         table_name,
-        s3_uri,
-    });
-    ope_client
-        .create_table(create_table_req)
+        uris,
+        accounts: args.accounts,
+        regions: args.regions,
+        force: args.force,
+        cache: args.cache || config.cache.enabled,
+        requester_pays: args.requester_pays || config.s3.requester_pays,
+        append: args.append,
+        provenance: args.provenance,
+        materialize_dir: args
+            .materialize
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        sample: args.sample.map(|size| SampleOptions {
+            percent: match size {
+                SampleSize::Percent(percent) => percent,
+                SampleSize::Rows(_) => 0.0,
+            },
+            rows: match size {
+                SampleSize::Percent(_) => 0,
+                SampleSize::Rows(rows) => rows,
+            },
+            method: args.sample_by.into(),
+            weight: args.sample_weight,
+        }),
+        gcs: match (args.gcs_key_id, args.gcs_secret) {
+            (Some(key_id), Some(secret)) => Some(GcsCredential {
+                key_id,
+                secret,
+                endpoint: args.gcs_endpoint.unwrap_or_default(),
+            }),
+            _ => None,
+        },
+        azure: match (args.azure_connection_string, args.azure_account_name) {
+            (Some(connection_string), _) => Some(AzureCredential {
+                connection_string,
+                account_name: String::new(),
+            }),
+            (None, Some(account_name)) => Some(AzureCredential {
+                connection_string: String::new(),
+                account_name,
+            }),
+            (None, None) => None,
+        },
+    };
+
+    if args.stdin {
+        let table_name = create_table_req.table_name.clone();
+        let (tx, rx) = mpsc::channel(STDIN_CHANNEL_SIZE);
+        tx.send(CreateTableStreamRequest {
+            payload: Some(Payload::Request(create_table_req)),
+        })
+        .await?;
+        let reader = tokio::spawn(send_stdin(tx));
+
+        let resp = ope_client
+            .create_table_from_stream(ReceiverStream::new(rx))
+            .await;
+        reader.await??;
+        let resp = resp.map_err(|e| e.message().to_string())?.into_inner();
+        print_summary(&resp);
+        warn_rejects(&table_name, resp.reject_count);
+
+        return Ok(());
+    }
+
+    if args.dry_run {
+        let resp = ope_client
+            .plan_load(Request::new(create_table_req))
+            .await
+            .map_err(|e| e.message().to_string())?
+            .into_inner();
+
+        let mut table = Table::new();
+        table.set_header(vec![
+            "objects",
+            "total size",
+            "first modified",
+            "last modified",
+        ]);
+        table.add_row(vec![
+            resp.object_count.to_string(),
+            format::bytes(resp.total_bytes),
+            resp.first_modified,
+            resp.last_modified,
+        ]);
+        println!("{}", table);
+
+        return Ok(());
+    }
+
+    if args.detach {
+        let table_name = create_table_req.table_name.clone();
+        let resp = ope_client
+            .start_load(Request::new(create_table_req))
+            .await
+            .map_err(|e| e.message().to_string())?
+            .into_inner();
+        info!(
+            "loading {} as job {} (see `lupe jobs status {}`)",
+            table_name, resp.job_id, resp.job_id
+        );
+
+        return Ok(());
+    }
+
+    let mut stream = ope_client
+        .create_table_with_progress(Request::new(create_table_req.clone()))
         .await
-        .map_err(|e| e.message().to_string())?;
+        .map_err(|e| e.message().to_string())?
+        .into_inner();
+    let bar = progress_bar()?;
+    let mut reply = None;
+    while let Some(resp) = stream.message().await.map_err(|e| {
+        bar.abandon();
+        e.message().to_string()
+    })? {
+        match resp.event {
+            Some(Event::Progress(progress)) => update_progress(&bar, &progress),
+            Some(Event::Reply(resp)) => reply = Some(resp),
+            None => {}
+        }
+    }
+    bar.finish_and_clear();
+    let resp = reply.ok_or("the server closed the stream without a reply")?;
+    if args.append {
+        info!("{}", resp.message);
+    }
+    if resp.object_count > 0 {
+        print_summary(&resp);
+    }
+    warn_rejects(&create_table_req.table_name, resp.reject_count);
+
+    if args.follow {
+        let table_name = create_table_req.table_name.clone();
+        let resp = ope_client
+            .start_follow(Request::new(StartFollowRequest {
+                request: Some(create_table_req),
+                interval_secs: args.interval.as_secs(),
+            }))
+            .await
+            .map_err(|e| e.message().to_string())?
+            .into_inner();
+        info!(
+            "following {} every {} as job {} (see `lupe jobs ls`)",
+            table_name,
+            humantime::format_duration(args.interval),
+            resp.job_id
+        );
+    }
 
     Ok(())
 }
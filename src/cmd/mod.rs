@@ -1,4 +1,11 @@
+pub(crate) mod cache;
 pub(crate) mod clean;
+pub(crate) mod describe;
+pub(crate) mod drop;
+pub(crate) mod jobs;
 pub(crate) mod load;
 pub(crate) mod query;
 pub(crate) mod server;
+pub(crate) mod snapshot;
+pub(crate) mod tables;
+pub(crate) mod view;
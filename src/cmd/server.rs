@@ -1,14 +1,21 @@
+use crate::pb::db::management_client::ManagementClient;
 use crate::pb::db::management_server::ManagementServer;
 use crate::pb::db::operation_server::OperationServer;
+use crate::pb::db::HealthCheckRequest;
 use crate::pb::{ManagementImpl, OperationImpl};
-use crate::util::uds::get_sock_path;
+use crate::repo::catalog;
+use crate::util::config;
+use crate::util::uds::{create_channel, get_sock_path};
 use duckdb::Connection;
+use std::path::{Path, PathBuf};
 use tokio::net::UnixListener;
+use tokio::process::Command;
 use tokio::signal;
 use tokio::sync::{mpsc, Mutex};
 use tonic::codegen::tokio_stream::wrappers::UnixListenerStream;
-use tonic::transport::Server;
-use tracing::{debug, info};
+use tonic::transport::{Channel, Server};
+use tonic::Request;
+use tracing::{debug, info, warn};
 
 async fn shutdown_signal(mut shutdown_rx: mpsc::Receiver<()>) {
     let ctrl_c_fut = async {
@@ -39,15 +46,64 @@ async fn shutdown_signal(mut shutdown_rx: mpsc::Receiver<()>) {
     std::fs::remove_file(get_sock_path()).expect("failed to remove socket file");
 }
 
-pub(crate) async fn run() -> Result<(), Box<dyn std::error::Error>> {
+// Starts the server unless it is already running, and returns a channel to it once it is up.
+pub(crate) async fn start(db_path: Option<&Path>) -> Result<Channel, Box<dyn std::error::Error>> {
+    if !get_sock_path().exists() {
+        info!("starting server...");
+        let mut command = Command::new(std::env::current_exe()?);
+        command.arg("server");
+        if let Some(db_path) = db_path {
+            command.arg("--db-path").arg(db_path);
+        }
+        let _child = command.spawn()?;
+        info!("server start successfully");
+    } else if db_path.is_some() {
+        warn!("server is already running, --db-path is ignored (run `lupe clean` to stop it)");
+    }
+
+    let channel = create_channel().await?;
+    let mut mgmt_client = ManagementClient::new(channel.clone());
+
+    let health_req = Request::new(HealthCheckRequest::default());
+    match mgmt_client.health_check(health_req).await {
+        Ok(resp) => {
+            debug!("response={:?}", resp);
+            debug!("server is up and running");
+        }
+        Err(e) => {
+            return Err(e.message().into());
+        }
+    }
+
+    Ok(channel)
+}
+
+pub(crate) async fn run(db_path: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
     let sock_path = get_sock_path();
     info!("listening on {:?}", sock_path);
 
+    let config = config::load()?;
+    debug!("config={:?}", config);
+
     let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
-    let db_conn = Mutex::new(Connection::open_in_memory()?);
+    let conn = match db_path.or(config.server.db_path) {
+        Some(db_path) => {
+            if let Some(dir) = db_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            info!("opening {:?}", db_path);
+            Connection::open(db_path)?
+        }
+        None => Connection::open_in_memory()?,
+    };
+    catalog::init(&conn)?;
+    let db_conn = Mutex::new(conn);
 
     let mgmt = ManagementImpl::builder().shutdown_tx(shutdown_tx).build();
-    let ope = OperationImpl::builder().db_conn(db_conn).build();
+    let ope = OperationImpl::builder()
+        .db_conn(db_conn)
+        .load_config(config.load)
+        .build();
 
     let uds = UnixListener::bind(sock_path)?;
     let uds_stream = UnixListenerStream::new(uds);
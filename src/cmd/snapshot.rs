@@ -0,0 +1,53 @@
+use crate::cmd::server;
+use crate::pb::db::operation_client::OperationClient;
+use crate::pb::db::{LoadSnapshotRequest, SaveSnapshotRequest};
+use crate::util::uds::create_channel;
+use clap::Subcommand;
+use std::path::PathBuf;
+use tonic::Request;
+use tracing::info;
+
+#[derive(Subcommand, Debug, Clone)]
+pub(crate) enum SnapshotCommands {
+    /// Save every table and the load catalog as Parquet files with a manifest
+    Save { dir: PathBuf },
+    /// Recreate the tables of a saved snapshot, starting the server if needed
+    Load { dir: PathBuf },
+}
+
+pub(crate) async fn run(command: SnapshotCommands) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        SnapshotCommands::Save { dir } => {
+            let dir = std::path::absolute(dir)?;
+            let mut client = OperationClient::new(create_channel().await?);
+
+            let resp = client
+                .save_snapshot(Request::new(SaveSnapshotRequest {
+                    dir: dir.to_string_lossy().to_string(),
+                }))
+                .await
+                .map_err(|e| e.message().to_string())?
+                .into_inner();
+
+            info!("saved {} tables to {:?}", resp.table_names.len(), dir);
+        }
+        SnapshotCommands::Load { dir } => {
+            let dir = std::path::absolute(dir)?;
+            let mut client = OperationClient::new(server::start(None).await?);
+
+            let resp = client
+                .load_snapshot(Request::new(LoadSnapshotRequest {
+                    dir: dir.to_string_lossy().to_string(),
+                }))
+                .await
+                .map_err(|e| e.message().to_string())?
+                .into_inner();
+
+            for table_name in resp.table_names {
+                info!("loaded {}", table_name);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use crate::pb::db::operation_client::OperationClient;
+use crate::pb::db::ListTablesRequest;
+use crate::util::format;
+use crate::util::uds::create_channel;
+use comfy_table::Table;
+use std::time::Duration;
+use tonic::Request;
+
+pub(crate) async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let channel = create_channel().await?;
+    let mut client = OperationClient::new(channel);
+
+    let resp = client
+        .list_tables(Request::new(ListTablesRequest::default()))
+        .await
+        .map_err(|e| e.message().to_string())?
+        .into_inner();
+
+    let mut table = Table::new();
+    table.set_header(vec![
+        "table",
+        "type",
+        "rows",
+        "size",
+        "duration",
+        "loaded at",
+        "sources",
+        "location",
+        "sample",
+    ]);
+    for info in resp.tables {
+        table.add_row(vec![
+            info.table_name,
+            info.table_type,
+            info.row_count.to_string(),
+            format::bytes(info.byte_count),
+            humantime::format_duration(Duration::from_millis(info.duration_ms)).to_string(),
+            info.loaded_at,
+            info.uris.join("\n"),
+            info.location,
+            info.sample,
+        ]);
+    }
+
+    println!("{}", table);
+
+    Ok(())
+}
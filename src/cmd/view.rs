@@ -0,0 +1,77 @@
+use crate::pb::db::operation_client::OperationClient;
+use crate::pb::db::{CreateViewRequest, DropViewsRequest, ListViewsRequest};
+use crate::util::uds::create_channel;
+use clap::Subcommand;
+use comfy_table::Table;
+use tonic::Request;
+use tracing::info;
+
+#[derive(Subcommand, Debug, Clone)]
+pub(crate) enum ViewCommands {
+    /// Save a query as a view e.g. lupe view create errors_5xx "SELECT * FROM alb_logs WHERE elb_status_code >= 500"
+    Create {
+        view_name: String,
+        sql: String,
+        /// Replace the view if it already exists
+        #[arg(long)]
+        replace: bool,
+    },
+    /// List saved views
+    Ls,
+    /// Remove saved views
+    Rm {
+        #[arg(required = true)]
+        view_names: Vec<String>,
+    },
+}
+
+pub(crate) async fn run(command: ViewCommands) -> Result<(), Box<dyn std::error::Error>> {
+    let channel = create_channel().await?;
+    let mut client = OperationClient::new(channel);
+
+    match command {
+        ViewCommands::Create {
+            view_name,
+            sql,
+            replace,
+        } => {
+            client
+                .create_view(Request::new(CreateViewRequest {
+                    view_name: view_name.clone(),
+                    sql,
+                    replace,
+                }))
+                .await
+                .map_err(|e| e.message().to_string())?;
+
+            info!("created {}", view_name);
+        }
+        ViewCommands::Ls => {
+            let resp = client
+                .list_views(Request::new(ListViewsRequest::default()))
+                .await
+                .map_err(|e| e.message().to_string())?
+                .into_inner();
+
+            let mut table = Table::new();
+            table.set_header(vec!["view", "created at", "sql"]);
+            for info in resp.views {
+                table.add_row(vec![info.view_name, info.created_at, info.sql]);
+            }
+            println!("{}", table);
+        }
+        ViewCommands::Rm { view_names } => {
+            let resp = client
+                .drop_views(Request::new(DropViewsRequest { view_names }))
+                .await
+                .map_err(|e| e.message().to_string())?
+                .into_inner();
+
+            for view_name in resp.view_names {
+                info!("removed {}", view_name);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -3,8 +3,13 @@ mod pb;
 mod repo;
 mod util;
 
-use crate::cmd::load::TableType;
+use crate::cmd::cache::CacheCommands;
+use crate::cmd::jobs::JobsCommands;
+use crate::cmd::load::LoadArgs;
+use crate::cmd::snapshot::SnapshotCommands;
+use crate::cmd::view::ViewCommands;
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 use tracing::error;
 
 #[derive(Parser)]
@@ -20,23 +25,49 @@ struct Cli {
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
     /// Load logs into DuckDB
-    Load {
-        #[arg(long, value_enum)]
-        table_type: TableType,
-        /// e.g. s3://bucket-name/path/to/**/*.log.gz
-        #[arg(long)]
-        s3_uri: String,
-        /// [default table name: alb: alb_logs, s3: s3_logs]
-        #[arg(long)]
-        table_name: Option<String>,
-    },
+    Load(Box<LoadArgs>),
     /// Clean up all tables
     Clean,
+    /// Manage the local cache of fetched log objects
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+    /// List loaded tables
+    Tables,
+    /// Show the columns of a table with their statistics and descriptions
+    Describe { table_name: String },
+    /// Drop tables while keeping the server and the other tables
+    Drop {
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        table_names: Vec<String>,
+        /// Drop all tables
+        #[arg(long)]
+        all: bool,
+    },
+    /// Manage background jobs such as `lupe load --follow` and `lupe load --detach`
+    Jobs {
+        #[command(subcommand)]
+        command: JobsCommands,
+    },
+    /// Save the tables to a directory, or load them from one e.g. to share an analysis
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommands,
+    },
+    /// Manage views saving named queries in the server
+    View {
+        #[command(subcommand)]
+        command: ViewCommands,
+    },
     /// Execute Raw Query
     Query { query: String },
     /// Start Server
     #[command(hide = true)]
-    Server,
+    Server {
+        #[arg(long)]
+        db_path: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -58,14 +89,17 @@ async fn main() {
     // You can check for the existence of subcommands, and if found use their
     // matches just as you would the top level cmd
     if let Err(e) = match &cli.command {
-        Commands::Load {
-            table_type,
-            table_name,
-            s3_uri,
-        } => cmd::load::run(table_type.clone(), table_name.clone(), s3_uri.to_string()).await,
+        Commands::Load(args) => cmd::load::run(*args.clone()).await,
         Commands::Clean => cmd::clean::run().await,
+        Commands::Cache { command } => cmd::cache::run(command.clone()).await,
+        Commands::Tables => cmd::tables::run().await,
+        Commands::Describe { table_name } => cmd::describe::run(table_name.clone()).await,
+        Commands::Drop { table_names, all } => cmd::drop::run(table_names.clone(), *all).await,
+        Commands::Jobs { command } => cmd::jobs::run(command.clone()).await,
+        Commands::Snapshot { command } => cmd::snapshot::run(command.clone()).await,
+        Commands::View { command } => cmd::view::run(command.clone()).await,
         Commands::Query { query } => cmd::query::run(query.clone()).await,
-        Commands::Server => cmd::server::run().await,
+        Commands::Server { db_path } => cmd::server::run(db_path.clone()).await,
     } {
         error!("{}", e);
         std::process::exit(1);
@@ -0,0 +1,1551 @@
+use crate::pb::db::{
+    CreateTableReply, CreateTableRequest, LoadProgress, SampleMethod, SampleOptions,
+};
+use crate::pb::new_client;
+use crate::repo::catalog::{CatalogEntry, IngestedObject};
+use crate::repo::object::{Object, ObjectSummary};
+use crate::repo::secret::Secrets;
+use crate::repo::{alb, catalog, object, table, Client, RowSample, Sample, Sources};
+use crate::util::cancel::Cancellation;
+use crate::util::config::LoadConfig;
+use crate::util::job::JobStatus;
+use duckdb::Connection;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::time::MissedTickBehavior;
+use tonic::Status;
+use tracing::{debug, warn};
+use typed_builder::TypedBuilder;
+
+#[derive(Debug)]
+pub(crate) enum LoadError {
+    InvalidArgument(String),
+    FailedPrecondition(String),
+    NotFound(String),
+    Cancelled(String),
+    Internal(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::InvalidArgument(message)
+            | LoadError::FailedPrecondition(message)
+            | LoadError::NotFound(message)
+            | LoadError::Cancelled(message)
+            | LoadError::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<LoadError> for Status {
+    fn from(value: LoadError) -> Self {
+        match value {
+            LoadError::InvalidArgument(message) => Status::invalid_argument(message),
+            LoadError::FailedPrecondition(message) => Status::failed_precondition(message),
+            LoadError::NotFound(message) => Status::not_found(message),
+            LoadError::Cancelled(message) => Status::cancelled(message),
+            LoadError::Internal(message) => Status::internal(message),
+        }
+    }
+}
+
+// uri of the logs uploaded from the client's stdin
+const STDIN: &str = "stdin";
+
+// objects are loaded in up to this many statements to report the progress between them
+const PROGRESS_BATCHES: usize = 20;
+
+#[derive(Debug, Default)]
+pub(crate) struct Loaded {
+    pub(crate) row_count: usize,
+    pub(crate) object_count: usize,
+    pub(crate) byte_count: u64,
+    pub(crate) duration: Duration,
+    pub(crate) reject_count: usize,
+    pub(crate) first_time: Option<String>,
+    pub(crate) last_time: Option<String>,
+}
+
+impl From<Loaded> for CreateTableReply {
+    fn from(value: Loaded) -> Self {
+        Self {
+            message: String::new(),
+            reject_count: value.reject_count as u64,
+            row_count: value.row_count as u64,
+            object_count: value.object_count as u64,
+            byte_count: value.byte_count,
+            duration_ms: value.duration.as_millis() as u64,
+            first_time: value.first_time.unwrap_or_default(),
+            last_time: value.last_time.unwrap_or_default(),
+        }
+    }
+}
+
+pub(crate) fn load_message(req: &CreateTableRequest, loaded: &Loaded) -> String {
+    if !req.append {
+        "OK".to_string()
+    } else if loaded.object_count == 0 {
+        "no new objects".to_string()
+    } else {
+        format!(
+            "appended {} rows from {} new objects",
+            loaded.row_count, loaded.object_count
+        )
+    }
+}
+
+pub(crate) fn source_uris(req: &CreateTableRequest) -> Result<Vec<String>, String> {
+    if req.uris.is_empty() {
+        return Err("uri is required".into());
+    }
+
+    let uris = match req.table_type {
+        0 => {
+            let mut uris = Vec::new();
+            for source in req.uris.iter() {
+                for uri in alb::expand_uri(source, &req.accounts, &req.regions)? {
+                    if !uris.contains(&uri) {
+                        uris.push(uri);
+                    }
+                }
+            }
+            uris
+        }
+        1 => {
+            if !req.accounts.is_empty() || !req.regions.is_empty() {
+                return Err("accounts and regions are only supported for alb".into());
+            }
+            req.uris.clone()
+        }
+        _ => return Err("invalid table type".into()),
+    };
+    debug!("uris={:?}", uris);
+
+    if uris.iter().any(|uri| object::is_gcs(uri)) && req.gcs.is_none() {
+        return Err("gcs key id and secret are required for gs:// uris".into());
+    }
+    if uris.iter().any(|uri| object::is_azure(uri)) && req.azure.is_none() {
+        return Err("azure connection string or account name is required for az:// uris".into());
+    }
+
+    Ok(uris)
+}
+
+pub(crate) fn ingested_objects(
+    conn: &Connection,
+    req: &CreateTableRequest,
+) -> duckdb::Result<HashSet<IngestedObject>> {
+    if req.append {
+        catalog::ingested_objects(conn, &req.table_name)
+    } else {
+        Ok(HashSet::new())
+    }
+}
+
+// Secrets are created for each request from its options, and only for the schemes being
+// loaded, so that uris which don't need them can be loaded without credentials. They are
+// dropped once the returned secrets are.
+pub(crate) fn create_secrets(
+    conn: &Connection,
+    uris: &[String],
+    req: &CreateTableRequest,
+) -> duckdb::Result<Secrets> {
+    let mut secrets = Secrets::new(conn)?;
+    let of = |is: fn(&str) -> bool| {
+        uris.iter()
+            .filter(|uri| is(uri))
+            .cloned()
+            .collect::<Vec<String>>()
+    };
+
+    let s3_uris = of(object::is_s3);
+    if !s3_uris.is_empty() {
+        secrets.create_s3(&s3_uris, req.requester_pays)?;
+    }
+    if let Some(gcs) = req.gcs.as_ref() {
+        let gcs_uris = of(object::is_gcs);
+        if !gcs_uris.is_empty() {
+            secrets.create_gcs(&gcs_uris, gcs)?;
+        }
+    }
+    if let Some(azure) = req.azure.as_ref() {
+        let azure_uris = of(object::is_azure);
+        if !azure_uris.is_empty() {
+            secrets.create_azure(&azure_uris, azure)?;
+        }
+    }
+
+    Ok(secrets)
+}
+
+fn record(
+    conn: &Connection,
+    entry: &CatalogEntry,
+    objects: &[IngestedObject],
+    append: bool,
+) -> duckdb::Result<()> {
+    if append {
+        catalog::append(conn, entry)?;
+    } else {
+        // entries of a table dropped by a raw query are left behind
+        catalog::remove(conn, entry.table_name())?;
+        catalog::insert(conn, entry)?;
+    }
+
+    catalog::insert_objects(conn, entry.table_name(), objects)
+}
+
+// Validates the sample of the request, and returns how it is recorded in the catalog.
+fn describe_sample(sample: &SampleOptions) -> Result<String, String> {
+    if sample.percent > 0.0 && sample.rows > 0 {
+        return Err("sample either a percentage or a number of rows".into());
+    }
+    if sample.rows == 0 && !(sample.percent > 0.0 && sample.percent <= 100.0) {
+        return Err("sample percentage must be greater than 0 and at most 100".into());
+    }
+
+    match (sample.method(), sample.rows) {
+        (SampleMethod::Objects, 0) => Ok(format!("{}% of objects", sample.percent)),
+        (SampleMethod::Objects, _) => Err("a number of rows can only be sampled by rows".into()),
+        (SampleMethod::Rows, 0) => Ok(format!("{}% of rows", sample.percent)),
+        (SampleMethod::Rows, _) if sample.weight => {
+            Err("sample weights need a percentage of rows, as the total is unknown".into())
+        }
+        (SampleMethod::Rows, rows) => Ok(format!("{} rows", rows)),
+    }
+}
+
+// a random `percent` of the items, at least one unless there are none
+fn sample_items<T>(mut items: Vec<T>, percent: f64, uri: impl Fn(&T) -> &str) -> Vec<T> {
+    let state = RandomState::new();
+    items.sort_by_cached_key(|item| state.hash_one(uri(item)));
+    items.truncate((items.len() as f64 * percent / 100.0).ceil() as usize);
+
+    items
+}
+
+fn eta(elapsed: Duration, done: u64, total: u64) -> Duration {
+    if done == 0 {
+        return Duration::ZERO;
+    }
+
+    elapsed.mul_f64(total.saturating_sub(done) as f64 / done as f64)
+}
+
+// Loads the uris in batches, fetching the cached objects of each batch first, and reports
+// the progress after each fetch and batch. The load stops once `progress` returns false.
+fn load_batches(
+    client: &dyn Client,
+    req: &CreateTableRequest,
+    source: &LoadSource,
+    location: Option<&str>,
+    batches: usize,
+    progress: &dyn Fn(LoadProgress) -> bool,
+) -> Result<usize, LoadError> {
+    let started = Instant::now();
+    let mut event = LoadProgress {
+        object_count: source.load_uris.len() as u64,
+        total_bytes: source.sizes.iter().sum(),
+        ..Default::default()
+    };
+    let cancelled = || LoadError::Cancelled(format!("load of {} was cancelled", req.table_name));
+    if !progress(event) {
+        return Err(cancelled());
+    }
+
+    let batch_size = source.load_uris.len().div_ceil(batches);
+    for (i, (batch, batch_sizes)) in source
+        .load_uris
+        .chunks(batch_size)
+        .zip(source.sizes.chunks(batch_size))
+        .enumerate()
+    {
+        for uri in batch {
+            let Some(object) = source.fetches.get(uri) else {
+                continue;
+            };
+            object::fetch(client.conn(), object, uri).map_err(|e| {
+                if progress(event) {
+                    LoadError::Internal(format!("failed to fetch {:?}: {}", object, e))
+                } else {
+                    cancelled()
+                }
+            })?;
+            event.objects_fetched += 1;
+            if !progress(event) {
+                return Err(cancelled());
+            }
+        }
+
+        let append = req.append || i > 0;
+        let row_count = match location {
+            Some(dir) => client.materialize(&req.table_name, batch, dir, append),
+            None if append => client.insert_into(&req.table_name, batch),
+            None => client.create_table(&req.table_name, batch),
+        }
+        .map_err(|e| {
+            if progress(event) {
+                LoadError::Internal(format!("failed to create table: {}", e))
+            } else {
+                cancelled()
+            }
+        })?;
+
+        event.objects_read += batch.len() as u64;
+        event.bytes_read += batch_sizes.iter().sum::<u64>();
+        event.row_count += row_count as u64;
+        event.eta_ms = if event.total_bytes > 0 {
+            eta(started.elapsed(), event.bytes_read, event.total_bytes)
+        } else {
+            eta(started.elapsed(), event.objects_read, event.object_count)
+        }
+        .as_millis() as u64;
+        if !progress(event) {
+            return Err(cancelled());
+        }
+    }
+
+    Ok(event.row_count as usize)
+}
+
+// Appending to a table which does not exist yet creates it instead.
+fn append_or_create(
+    conn: &Connection,
+    req: &CreateTableRequest,
+) -> Result<CreateTableRequest, LoadError> {
+    let mut req = req.clone();
+    if req.append {
+        let table_names = table::list(conn)
+            .map_err(|e| LoadError::Internal(format!("failed to list tables: {}", e)))?;
+        req.append = table_names.contains(&req.table_name);
+    }
+
+    Ok(req)
+}
+
+// Returns the directory the rows are materialized to, which is the one of the table when
+// appending, after checking that the table can be appended to.
+fn target_location(
+    conn: &Connection,
+    req: &CreateTableRequest,
+    table_type: &str,
+) -> Result<Option<String>, LoadError> {
+    let mut location = Some(req.materialize_dir.clone()).filter(|dir| !dir.is_empty());
+    if req.append {
+        let loaded_type = catalog::table_type(conn, &req.table_name)
+            .map_err(|e| LoadError::Internal(format!("failed to get table type: {}", e)))?;
+        if loaded_type.is_some_and(|loaded_type| loaded_type != table_type) {
+            return Err(LoadError::FailedPrecondition(format!(
+                "{} was not loaded as {}",
+                req.table_name, table_type
+            )));
+        }
+
+        // appends go to the files of materialized tables
+        let loaded_location = catalog::location(conn, &req.table_name)
+            .map_err(|e| LoadError::Internal(format!("failed to get location: {}", e)))?;
+        if location.is_some() && location != loaded_location {
+            return Err(LoadError::FailedPrecondition(format!(
+                "{} was not materialized to {}",
+                req.table_name, req.materialize_dir
+            )));
+        }
+        location = loaded_location;
+    }
+
+    // the line numbers of rows are corrected after the rejects are known
+    if location.is_some() && req.provenance {
+        return Err(LoadError::InvalidArgument(
+            "provenance cannot be added to materialized tables".into(),
+        ));
+    }
+
+    Ok(location)
+}
+
+// what a load reads, and how it is recorded in the catalog
+#[derive(TypedBuilder)]
+struct LoadSource {
+    // uris of the request, recorded in the catalog
+    uris: Vec<String>,
+    // uris read by the load, e.g. the local copies of cached objects, and their sizes
+    load_uris: Vec<String>,
+    sizes: Vec<u64>,
+    // objects fetched to the cache before their local copies are read
+    #[builder(default)]
+    fetches: HashMap<String, Object>,
+    #[builder(default)]
+    objects: Vec<IngestedObject>,
+    byte_count: u64,
+    #[builder(default)]
+    sample: Option<String>,
+    #[builder(default)]
+    row_sample: Sample,
+    #[builder(default)]
+    sources: Sources,
+}
+
+// The files of a materialized table before a load, so that a failed load can remove the
+// files it wrote.
+struct WrittenFiles {
+    dir: PathBuf,
+    existed: bool,
+    before: HashSet<PathBuf>,
+}
+
+impl WrittenFiles {
+    fn before(dir: &str) -> std::io::Result<Self> {
+        let dir = PathBuf::from(dir);
+        let mut before = HashSet::new();
+        let existed = dir.exists();
+        if existed {
+            list_files(&dir, &mut before)?;
+        }
+
+        Ok(Self {
+            dir,
+            existed,
+            before,
+        })
+    }
+
+    fn remove(&self) -> std::io::Result<()> {
+        if !self.existed {
+            return match std::fs::remove_dir_all(&self.dir) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        let mut files = HashSet::new();
+        list_files(&self.dir, &mut files)?;
+        for file in files.difference(&self.before) {
+            std::fs::remove_file(file)?;
+        }
+        remove_empty_dirs(&self.dir)?;
+
+        Ok(())
+    }
+}
+
+fn list_files(dir: &Path, files: &mut HashSet<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+
+    Ok(())
+}
+
+// e.g. the partitions of a failed load
+fn remove_empty_dirs(dir: &Path) -> std::io::Result<bool> {
+    let mut empty = true;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() && remove_empty_dirs(&path)? {
+            std::fs::remove_dir(&path)?;
+        } else {
+            empty = false;
+        }
+    }
+
+    Ok(empty)
+}
+
+// Loads the source into the table in a transaction, so that a failed load leaves no rows
+// or files behind, and records it.
+fn ingest(
+    conn: Connection,
+    req: &CreateTableRequest,
+    mut source: LoadSource,
+    location: Option<String>,
+    started: Instant,
+    progress: &dyn Fn(LoadProgress) -> bool,
+) -> Result<Loaded, LoadError> {
+    // a number of rows is sampled from all the objects at once
+    let batches = match source.row_sample.rows() {
+        Some(RowSample::Rows(_)) => 1,
+        _ => PROGRESS_BATCHES,
+    };
+    let client = new_client(
+        req.table_type,
+        req.provenance,
+        std::mem::take(&mut source.row_sample),
+        std::mem::take(&mut source.sources),
+        conn,
+    )
+    .map_err(LoadError::InvalidArgument)?;
+    let written = location
+        .as_deref()
+        .map(WrittenFiles::before)
+        .transpose()
+        .map_err(|e| LoadError::Internal(format!("failed to list materialized files: {}", e)))?;
+    // The load is recorded in the same transaction, so that a table dropped while loading
+    // fails the commit instead of being recorded again.
+    client
+        .conn()
+        .execute_batch("BEGIN TRANSACTION;")
+        .map_err(|e| LoadError::Internal(format!("failed to begin transaction: {}", e)))?;
+    let result = load_batches(
+        client.as_ref(),
+        req,
+        &source,
+        location.as_deref(),
+        batches,
+        progress,
+    )
+    .and_then(|row_count| {
+        let reject_count = client
+            .store_rejects(
+                &req.table_name,
+                &source.load_uris,
+                req.append,
+                req.provenance,
+            )
+            .map_err(|e| LoadError::Internal(format!("failed to store rejects: {}", e)))?;
+        let (first_time, last_time) = client
+            .time_range(&req.table_name)
+            .map_err(|e| LoadError::Internal(format!("failed to get time range: {}", e)))?;
+
+        let entry = CatalogEntry::builder()
+            .table_name(req.table_name.clone())
+            .table_type(req.table_type().as_str_name().to_lowercase())
+            .uris(source.uris)
+            .row_count(row_count as u64)
+            .byte_count(source.byte_count)
+            .duration(started.elapsed())
+            .location(location.clone())
+            .sample(source.sample)
+            .build();
+        record(client.conn(), &entry, &source.objects, req.append)
+            .map_err(|e| LoadError::Internal(format!("failed to record {:?}: {}", entry, e)))?;
+
+        Ok(Loaded {
+            row_count,
+            object_count: source.load_uris.len(),
+            byte_count: entry.byte_count(),
+            duration: started.elapsed(),
+            reject_count,
+            first_time,
+            last_time,
+        })
+    });
+
+    match result {
+        Ok(loaded) => {
+            client
+                .conn()
+                .execute_batch("COMMIT;")
+                .map_err(|e| LoadError::Internal(format!("failed to commit: {}", e)))?;
+            Ok(loaded)
+        }
+        Err(e) => {
+            if let Err(e) = client.conn().execute_batch("ROLLBACK;") {
+                warn!("failed to roll back the load of {}: {}", req.table_name, e);
+            }
+            if let Some(Err(e)) = written.map(|written| written.remove()) {
+                warn!("failed to remove the files of {}: {}", req.table_name, e);
+            }
+            Err(e)
+        }
+    }
+}
+
+// Loads the objects matching the request into a new table, or the objects which have
+// not been loaded yet into the existing table when appending.
+pub(crate) fn load(
+    conn: Connection,
+    req: &CreateTableRequest,
+    load_config: &LoadConfig,
+    progress: &dyn Fn(LoadProgress) -> bool,
+) -> Result<Loaded, LoadError> {
+    let started = Instant::now();
+    let req = &append_or_create(&conn, req)?;
+    let uris = source_uris(req).map_err(LoadError::InvalidArgument)?;
+    let sample = req
+        .sample
+        .as_ref()
+        .map(describe_sample)
+        .transpose()
+        .map_err(LoadError::InvalidArgument)?;
+    let table_type = req.table_type().as_str_name().to_lowercase();
+
+    let _secrets = create_secrets(&conn, &uris, req)
+        .map_err(|e| LoadError::Internal(format!("failed to create secret: {}", e)))?;
+
+    let location = target_location(&conn, req, &table_type)?;
+    let ingested = ingested_objects(&conn, req)
+        .map_err(|e| LoadError::Internal(format!("failed to get ingested objects: {}", e)))?;
+
+    let (http_uris, listable_uris): (Vec<String>, Vec<String>) =
+        uris.iter().cloned().partition(|uri| object::is_http(uri));
+    let mut http_uris = http_uris
+        .into_iter()
+        .filter(|uri| !ingested.contains(&IngestedObject::builder().uri(uri.clone()).build()))
+        .collect::<Vec<String>>();
+
+    // The listed objects are loaded rather than the uris, so that objects delivered
+    // in the meantime are left to the next append instead of being loaded twice.
+    let objects = if listable_uris.is_empty() {
+        Vec::new()
+    } else {
+        object::list(&conn, &listable_uris)
+            .map_err(|e| LoadError::Internal(format!("failed to list objects: {}", e)))?
+    };
+    let mut objects = objects
+        .into_iter()
+        .filter(|object| !ingested.contains(&object.into()))
+        .collect::<Vec<Object>>();
+
+    let mut row_sample = Sample::default();
+    if let Some(options) = req.sample.as_ref() {
+        let weight = match options.method() {
+            SampleMethod::Objects => {
+                let matched_count = objects.len() + http_uris.len();
+                objects = sample_items(objects, options.percent, |object| object.uri());
+                http_uris = sample_items(http_uris, options.percent, |uri| uri);
+                matched_count as f64 / (objects.len() + http_uris.len()).max(1) as f64
+            }
+            SampleMethod::Rows => 100.0 / options.percent,
+        };
+        row_sample = Sample::builder()
+            .rows(match options.method() {
+                SampleMethod::Objects => None,
+                SampleMethod::Rows if options.rows > 0 => Some(RowSample::Rows(options.rows)),
+                SampleMethod::Rows => Some(RowSample::Percent(options.percent)),
+            })
+            .weight(Some(weight).filter(|_| options.weight))
+            .build();
+    }
+
+    if objects.is_empty() && http_uris.is_empty() {
+        return if req.append {
+            Ok(Loaded::default())
+        } else {
+            Err(LoadError::NotFound("no objects matched the uris".into()))
+        };
+    }
+
+    let mut summary = ObjectSummary::from(objects.as_slice());
+    summary.add_unlisted(http_uris.len() as u64);
+    if !req.force {
+        summary
+            .check_limits(load_config)
+            .map_err(LoadError::FailedPrecondition)?;
+    }
+
+    let mut load_uris = Vec::new();
+    let mut sizes = Vec::new();
+    let mut sources = Sources::default();
+    let mut fetches = HashMap::new();
+    for object in objects.iter() {
+        match object::cache_path(object).filter(|_| req.cache) {
+            Some(path) => {
+                sources.insert(path.clone(), object.uri().to_string());
+                fetches.insert(path.clone(), object.clone());
+                load_uris.push(path);
+            }
+            None => load_uris.push(object.uri().to_string()),
+        }
+        sizes.push(object.size());
+    }
+    load_uris.extend(http_uris.iter().cloned());
+    sizes.resize(load_uris.len(), 0);
+
+    let mut loaded = objects
+        .iter()
+        .map(|object| object.into())
+        .collect::<Vec<IngestedObject>>();
+    loaded.extend(
+        http_uris
+            .into_iter()
+            .map(|uri| IngestedObject::builder().uri(uri).build()),
+    );
+
+    let source = LoadSource::builder()
+        .uris(uris)
+        .load_uris(load_uris)
+        .sizes(sizes)
+        .objects(loaded)
+        .byte_count(summary.total_bytes())
+        .sample(sample)
+        .row_sample(row_sample)
+        .fetches(fetches)
+        .sources(sources)
+        .build();
+    ingest(conn, req, source, location, started, progress)
+}
+
+// Loads a file uploaded by the client from its stdin, whose request has been validated
+// when the upload started.
+pub(crate) fn load_upload(
+    conn: Connection,
+    req: &CreateTableRequest,
+    path: String,
+    byte_count: u64,
+    started: Instant,
+    progress: &dyn Fn(LoadProgress) -> bool,
+) -> Result<Loaded, LoadError> {
+    let req = &append_or_create(&conn, req)?;
+    let table_type = req.table_type().as_str_name().to_lowercase();
+    let location = target_location(&conn, req, &table_type)?;
+
+    let mut sources = Sources::default();
+    sources.insert(path.clone(), STDIN.to_string());
+    let source = LoadSource::builder()
+        .uris(vec![STDIN.to_string()])
+        .load_uris(vec![path])
+        .sizes(vec![byte_count])
+        .byte_count(byte_count)
+        .sources(sources)
+        .build();
+    ingest(conn, req, source, location, started, progress)
+}
+
+// Loads in the background as a job, reporting the progress to its status.
+pub(crate) async fn detach(
+    conn: Connection,
+    req: CreateTableRequest,
+    load_config: LoadConfig,
+    status: Arc<Mutex<JobStatus>>,
+) {
+    let progress_status = status.clone();
+    let table_name = req.table_name.clone();
+    let result = Cancellation::new(&conn)
+        .run_blocking(move || {
+            load(conn, &req, &load_config, &|progress| {
+                let mut status = progress_status.blocking_lock();
+                status.progressed(progress);
+                !status.is_cancelled()
+            })
+        })
+        .await
+        .unwrap_or_else(|e| Err(LoadError::Internal(format!("{}", e))));
+
+    let mut status = status.lock().await;
+    match result {
+        Ok(loaded) => {
+            debug!(
+                "loaded {} rows from {} objects into {}, rejected {} lines",
+                loaded.row_count, loaded.object_count, table_name, loaded.reject_count
+            );
+            status.succeeded(loaded.row_count as u64);
+        }
+        Err(e) => {
+            warn!("failed to load {}: {}", table_name, e);
+            status.failed(e.to_string());
+        }
+    }
+    status.finish();
+}
+
+// Appends the objects delivered since the previous run every interval until the job is
+// cancelled. The first run is an interval after the initial load made by the client.
+pub(crate) async fn follow(
+    conn: Connection,
+    req: CreateTableRequest,
+    load_config: LoadConfig,
+    interval: Duration,
+    status: Arc<Mutex<JobStatus>>,
+) {
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        let result = match conn.try_clone() {
+            Ok(conn) => {
+                let req = req.clone();
+                let load_config = load_config.clone();
+                let status = status.clone();
+                Cancellation::new(&conn)
+                    .run_blocking(move || {
+                        load(conn, &req, &load_config, &|_| {
+                            !status.blocking_lock().is_cancelled()
+                        })
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(LoadError::Internal(format!("{}", e))))
+            }
+            Err(e) => Err(LoadError::Internal(format!(
+                "failed to get connection: {}",
+                e
+            ))),
+        };
+
+        let mut status = status.lock().await;
+        match result {
+            Ok(loaded) => {
+                debug!(
+                    "appended {} rows from {} new objects to {}, rejected {} lines",
+                    loaded.row_count, loaded.object_count, req.table_name, loaded.reject_count
+                );
+                status.succeeded(loaded.row_count as u64);
+            }
+            Err(e) => {
+                warn!("failed to follow {}: {}", req.table_name, e);
+                status.failed(e.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::db::{AzureCredential, GcsCredential, TableInfo, TableType};
+    use crate::repo::tests::{connection, LogFile, ALB_LOG_LINE, S3_LOG_LINE};
+    use std::path::Path;
+
+    fn request(table_type: TableType, append: bool) -> CreateTableRequest {
+        CreateTableRequest {
+            table_type: table_type.into(),
+            table_name: "stdin_logs".to_string(),
+            append,
+            ..Default::default()
+        }
+    }
+
+    fn upload(
+        conn: &Connection,
+        req: &CreateTableRequest,
+        logs: &LogFile,
+    ) -> Result<Loaded, LoadError> {
+        load_upload(
+            conn.try_clone().unwrap(),
+            req,
+            logs.uri(),
+            0,
+            Instant::now(),
+            &|_| true,
+        )
+    }
+
+    fn count(conn: &Connection, table_name: &str) -> usize {
+        conn.query_row(
+            format!("SELECT count(*) FROM {};", table_name).as_str(),
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn load_upload_appends_through_load_pipeline() {
+        let conn = connection();
+        let logs = LogFile::create("upload", &[ALB_LOG_LINE, "not a log line"]);
+
+        let req = request(TableType::Alb, false);
+        let loaded = upload(&conn, &req, &logs).unwrap();
+        assert_eq!(load_message(&req, &loaded), "OK");
+        assert_eq!((loaded.row_count, loaded.reject_count), (1, 1));
+
+        let req = request(TableType::Alb, true);
+        let loaded = upload(&conn, &req, &logs).unwrap();
+        assert_eq!(
+            load_message(&req, &loaded),
+            "appended 1 rows from 1 new objects"
+        );
+        assert_eq!(count(&conn, "stdin_logs"), 2);
+        assert_eq!(count(&conn, "stdin_logs_rejects"), 2);
+    }
+
+    #[test]
+    fn load_upload_records_stdin_as_source_file() {
+        let conn = connection();
+        let logs = LogFile::create(
+            "upload-provenance",
+            &[S3_LOG_LINE, "not an access log line"],
+        );
+        let req = CreateTableRequest {
+            provenance: true,
+            ..request(TableType::S3, false)
+        };
+        upload(&conn, &req, &logs).unwrap();
+
+        let source_files = |table_name: &str| -> String {
+            conn.query_row(
+                format!(
+                    "SELECT string_agg(DISTINCT source_file) FROM {};",
+                    table_name
+                )
+                .as_str(),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(source_files("stdin_logs"), "stdin");
+        assert_eq!(source_files("stdin_logs_rejects"), "stdin");
+    }
+
+    #[test]
+    fn load_upload_refuses_appending_other_table_type() {
+        let conn = connection();
+        let logs = LogFile::create("upload-type", &[S3_LOG_LINE]);
+
+        upload(&conn, &request(TableType::S3, false), &logs).unwrap();
+        let result = upload(&conn, &request(TableType::Alb, true), &logs);
+
+        assert!(matches!(result, Err(LoadError::FailedPrecondition(_))));
+        assert_eq!(count(&conn, "stdin_logs"), 1);
+    }
+
+    #[test]
+    fn load_upload_appends_s3_logs() {
+        let conn = connection();
+        let logs = LogFile::create("upload-s3", &[S3_LOG_LINE]);
+
+        for append in [false, true] {
+            upload(&conn, &request(TableType::S3, append), &logs).unwrap();
+        }
+
+        assert_eq!(count(&conn, "stdin_logs"), 2);
+    }
+
+    fn glob_request(logs: &LogFile, name: &str) -> CreateTableRequest {
+        CreateTableRequest {
+            uris: vec![logs.uri().replace(name, "limits-*")],
+            ..request(TableType::Alb, false)
+        }
+    }
+
+    #[test]
+    fn load_refuses_objects_over_limits_unless_forced() {
+        let conn = connection();
+        let logs = LogFile::create("limits-a", &[ALB_LOG_LINE]);
+        let _more_logs = LogFile::create("limits-b", &[ALB_LOG_LINE]);
+        let req = glob_request(&logs, "limits-a");
+        let load_with = |load_config: &LoadConfig, req: &CreateTableRequest| {
+            load(conn.try_clone().unwrap(), req, load_config, &|_| true)
+        };
+
+        let too_many = LoadConfig {
+            max_objects: 1,
+            ..Default::default()
+        };
+        let too_large = LoadConfig {
+            max_bytes: ALB_LOG_LINE.len() as u64,
+            ..Default::default()
+        };
+        for load_config in [&too_many, &too_large] {
+            let result = load_with(load_config, &req);
+            assert!(matches!(result, Err(LoadError::FailedPrecondition(_))));
+        }
+
+        let forced = CreateTableRequest {
+            force: true,
+            ..req.clone()
+        };
+        let loaded = load_with(&too_many, &forced).unwrap();
+        assert_eq!((loaded.object_count, loaded.row_count), (2, 2));
+    }
+
+    #[test]
+    fn urls_count_towards_object_limit() {
+        let load_config = LoadConfig {
+            max_objects: 2,
+            ..Default::default()
+        };
+        let req = CreateTableRequest {
+            uris: (0..3)
+                .map(|i| format!("https://example.com/{}.log?X-Amz-Signature=abc", i))
+                .collect(),
+            ..request(TableType::Alb, false)
+        };
+
+        let result = load(connection(), &req, &load_config, &|_| true);
+        assert!(
+            matches!(&result, Err(LoadError::FailedPrecondition(message)) if message.contains("3 objects")),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn cached_objects_are_fetched_with_their_batch() {
+        let conn = connection();
+        let logs = [
+            LogFile::create("fetch-a", &[ALB_LOG_LINE]),
+            LogFile::create("fetch-b", &[ALB_LOG_LINE]),
+        ];
+        let objects = object::list(&conn, &[logs[0].uri(), logs[1].uri()]).unwrap();
+        // local copies, named like cached files
+        let paths = ["a", "b"].map(|name| {
+            std::env::temp_dir()
+                .join(format!(
+                    "lupe-test-{}-fetched{}.log",
+                    std::process::id(),
+                    name
+                ))
+                .to_string_lossy()
+                .to_string()
+        });
+        let source = || {
+            let mut sources = Sources::default();
+            for (path, object) in paths.iter().zip(objects.iter()) {
+                sources.insert(path.clone(), object.uri().to_string());
+            }
+            LoadSource::builder()
+                .uris(vec![])
+                .load_uris(paths.to_vec())
+                .sizes(objects.iter().map(|object| object.size()).collect())
+                .fetches(paths.iter().cloned().zip(objects.iter().cloned()).collect())
+                .byte_count(0)
+                .sources(sources)
+                .build()
+        };
+        let req = CreateTableRequest {
+            provenance: true,
+            ..request(TableType::Alb, false)
+        };
+        let events = std::sync::Mutex::new(Vec::new());
+        let ingest_until = |fetched: u64| {
+            events.lock().unwrap().clear();
+            ingest(
+                conn.try_clone().unwrap(),
+                &req,
+                source(),
+                None,
+                Instant::now(),
+                &|event| {
+                    events
+                        .lock()
+                        .unwrap()
+                        .push((event.objects_fetched, event.objects_read));
+                    event.objects_fetched < fetched
+                },
+            )
+        };
+
+        let cancelled = ingest_until(1);
+        let fetched = paths.clone().map(|path| Path::new(&path).exists());
+        assert!(matches!(cancelled, Err(LoadError::Cancelled(_))));
+        assert_eq!(*events.lock().unwrap(), [(0, 0), (1, 0)]);
+        assert_eq!(fetched, [true, false]);
+
+        let loaded = ingest_until(u64::MAX).unwrap();
+        let source_files = conn
+            .prepare("SELECT source_file FROM stdin_logs ORDER BY source_file")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<duckdb::Result<Vec<String>>>()
+            .unwrap();
+        for path in paths.iter() {
+            std::fs::remove_file(path).unwrap();
+            std::fs::remove_file(Path::new(path).with_extension("toml")).unwrap();
+        }
+        assert_eq!(loaded.row_count, 2);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [(0, 0), (1, 0), (1, 1), (2, 1), (2, 2)]
+        );
+        assert_eq!(source_files, [logs[0].uri(), logs[1].uri()]);
+    }
+
+    // Serves `content` at any path, with the range requests httpfs reads with, and returns
+    // the url of the server.
+    fn serve(content: &'static str) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut request = Vec::new();
+                    let mut line = String::new();
+                    while std::io::BufRead::read_line(&mut reader, &mut line).unwrap_or(0) > 2 {
+                        request.push(std::mem::take(&mut line));
+                    }
+                    let Some(request_line) = request.first() else {
+                        break;
+                    };
+                    let range = request.iter().find_map(|header| {
+                        let range = header.to_lowercase();
+                        let (start, end) = range
+                            .trim()
+                            .strip_prefix("range: bytes=")?
+                            .split_once('-')?;
+                        Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?))
+                    });
+                    let (status, body, content_range) = match range {
+                        Some((start, end)) => {
+                            let end = end.min(content.len() - 1);
+                            (
+                                "206 Partial Content",
+                                &content[start..=end],
+                                format!(
+                                    "Content-Range: bytes {}-{}/{}\r\n",
+                                    start,
+                                    end,
+                                    content.len()
+                                ),
+                            )
+                        }
+                        None => ("200 OK", content, String::new()),
+                    };
+                    let head = format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n{}\r\n",
+                        status,
+                        body.len(),
+                        content_range
+                    );
+                    let mut response = head.into_bytes();
+                    if !request_line.starts_with("HEAD") {
+                        response.extend_from_slice(body.as_bytes());
+                    }
+                    if std::io::Write::write_all(&mut stream, &response).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    #[ignore = "needs the httpfs extension"]
+    fn http_urls_are_loaded_and_appended_once() {
+        let conn = connection();
+        conn.execute_batch("LOAD httpfs;").unwrap();
+        let url = serve(Box::leak(format!("{}\n", ALB_LOG_LINE).into_boxed_str()));
+        let uris = ["a", "b"]
+            .map(|name| format!("{}/{}.log?X-Amz-Signature=abc", url, name))
+            .to_vec();
+        let req = CreateTableRequest {
+            uris: uris.clone(),
+            provenance: true,
+            ..request(TableType::Alb, false)
+        };
+        let load_config = LoadConfig::default();
+
+        let loaded = load(conn.try_clone().unwrap(), &req, &load_config, &|_| true).unwrap();
+        assert_eq!((loaded.object_count, loaded.row_count), (2, 2));
+        let source_files = conn
+            .prepare("SELECT source_file FROM stdin_logs ORDER BY source_file")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<duckdb::Result<Vec<String>>>()
+            .unwrap();
+        assert_eq!(source_files, uris);
+
+        let req = CreateTableRequest {
+            append: true,
+            ..req
+        };
+        let loaded = load(conn.try_clone().unwrap(), &req, &load_config, &|_| true).unwrap();
+        assert_eq!(load_message(&req, &loaded), "no new objects");
+        assert_eq!(count(&conn, "stdin_logs"), 2);
+    }
+
+    #[test]
+    fn source_uris_need_credentials_of_their_schemes() {
+        let req = |uri: &str| CreateTableRequest {
+            uris: vec![uri.to_string()],
+            ..request(TableType::S3, false)
+        };
+        let gcs = GcsCredential {
+            key_id: "GOOG1EXAMPLE".to_string(),
+            secret: "secret".to_string(),
+            ..Default::default()
+        };
+        let azure = AzureCredential {
+            account_name: "account".to_string(),
+            ..Default::default()
+        };
+
+        assert!(source_uris(&req("gs://bucket/key")).is_err());
+        assert!(source_uris(&req("gcs://bucket/key")).is_err());
+        assert!(source_uris(&req("az://container/key")).is_err());
+        assert!(source_uris(&CreateTableRequest {
+            gcs: Some(gcs),
+            ..req("gs://bucket/key")
+        })
+        .is_ok());
+        assert!(source_uris(&CreateTableRequest {
+            azure: Some(azure),
+            ..req("az://container/key")
+        })
+        .is_ok());
+    }
+
+    // Loads the ALB logs at $LUPE_TEST_<emulator>_URI, e.g. gs://bucket/alb/*.log uploaded
+    // to fake-gcs-server or az://container/alb/*.log uploaded to Azurite.
+    fn load_from_emulator(emulator: &str, req: CreateTableRequest) {
+        let var = |name: &str| {
+            let var = format!("LUPE_TEST_{}_{}", emulator, name);
+            std::env::var(&var).unwrap_or_else(|_| panic!("{} is not set", var))
+        };
+        let req = CreateTableRequest {
+            uris: vec![var("URI")],
+            ..req
+        };
+        let conn = connection();
+        conn.execute_batch("LOAD httpfs;").unwrap();
+
+        let loaded = load(conn, &req, &LoadConfig::default(), &|_| true).unwrap();
+        assert!(loaded.object_count > 0);
+        assert!(loaded.row_count > 0);
+    }
+
+    #[test]
+    #[ignore = "needs fake-gcs-server at $LUPE_TEST_GCS_ENDPOINT"]
+    fn gcs_objects_are_loaded_from_emulator() {
+        let endpoint = std::env::var("LUPE_TEST_GCS_ENDPOINT").unwrap();
+        load_from_emulator(
+            "GCS",
+            CreateTableRequest {
+                gcs: Some(GcsCredential {
+                    key_id: "GOOG1EXAMPLE".to_string(),
+                    secret: "secret".to_string(),
+                    endpoint,
+                }),
+                ..request(TableType::Alb, false)
+            },
+        );
+    }
+
+    #[test]
+    #[ignore = "needs Azurite at $LUPE_TEST_AZURE_CONNECTION_STRING"]
+    fn azure_objects_are_loaded_from_emulator() {
+        let connection_string = std::env::var("LUPE_TEST_AZURE_CONNECTION_STRING").unwrap();
+        load_from_emulator(
+            "AZURE",
+            CreateTableRequest {
+                azure: Some(AzureCredential {
+                    connection_string,
+                    ..Default::default()
+                }),
+                ..request(TableType::Alb, false)
+            },
+        );
+    }
+
+    struct MaterializeDir(PathBuf);
+
+    impl MaterializeDir {
+        fn create(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("lupe-test-{}-{}", std::process::id(), name)))
+        }
+
+        fn files(&self) -> HashSet<PathBuf> {
+            let mut files = HashSet::new();
+            if self.0.exists() {
+                list_files(&self.0, &mut files).unwrap();
+            }
+            files
+        }
+    }
+
+    impl Drop for MaterializeDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn materialize_request(uris: Vec<String>, dir: &MaterializeDir) -> CreateTableRequest {
+        CreateTableRequest {
+            uris,
+            materialize_dir: dir.0.to_string_lossy().to_string(),
+            ..request(TableType::Alb, false)
+        }
+    }
+
+    #[test]
+    fn cancelled_materialized_load_removes_its_files() {
+        let conn = connection();
+        let dir = MaterializeDir::create("materialized");
+        let logs = LogFile::create("materialize-a", &[ALB_LOG_LINE]);
+        let _more_logs = LogFile::create("materialize-b", &[ALB_LOG_LINE]);
+        let req = materialize_request(
+            vec![logs.uri().replace("materialize-a", "materialize-*")],
+            &dir,
+        );
+        let load_until = |objects_read: u64| {
+            load(
+                conn.try_clone().unwrap(),
+                &req,
+                &LoadConfig::default(),
+                &|event| event.objects_read < objects_read,
+            )
+        };
+
+        // cancelled once the first of the two batches is written
+        let cancelled = load_until(1);
+        assert!(matches!(cancelled, Err(LoadError::Cancelled(_))));
+        assert!(!dir.0.exists());
+        assert!(table::list(&conn).unwrap().is_empty());
+
+        let loaded = load_until(u64::MAX).unwrap();
+        assert_eq!(loaded.row_count, 2);
+        assert_eq!(count(&conn, "stdin_logs"), 2);
+        assert!(!dir.files().is_empty());
+        assert_eq!(
+            catalog::location(&conn, "stdin_logs").unwrap(),
+            Some(req.materialize_dir.clone())
+        );
+    }
+
+    #[test]
+    fn provenance_is_refused_for_materialized_tables() {
+        let conn = connection();
+        let dir = MaterializeDir::create("provenance");
+        let logs = LogFile::create("materialize-provenance", &[ALB_LOG_LINE]);
+        let req = materialize_request(vec![logs.uri()], &dir);
+        load(
+            conn.try_clone().unwrap(),
+            &req,
+            &LoadConfig::default(),
+            &|_| true,
+        )
+        .unwrap();
+
+        for req in [
+            CreateTableRequest {
+                provenance: true,
+                ..req.clone()
+            },
+            // appends go to the files of the table
+            CreateTableRequest {
+                provenance: true,
+                append: true,
+                materialize_dir: String::new(),
+                ..req.clone()
+            },
+        ] {
+            let result = load(
+                conn.try_clone().unwrap(),
+                &req,
+                &LoadConfig::default(),
+                &|_| true,
+            );
+            assert!(matches!(result, Err(LoadError::InvalidArgument(_))));
+        }
+    }
+
+    #[test]
+    fn append_materializes_missing_table_and_keeps_its_files_when_cancelled() {
+        let conn = connection();
+        let dir = MaterializeDir::create("appended");
+        let logs = [
+            LogFile::create("append-materialize-a", &[ALB_LOG_LINE]),
+            LogFile::create("append-materialize-b", &[ALB_LOG_LINE]),
+            LogFile::create("append-materialize-c", &[ALB_LOG_LINE]),
+        ];
+        let append = |uris: Vec<String>, objects_read: u64| {
+            let req = CreateTableRequest {
+                append: true,
+                ..materialize_request(uris, &dir)
+            };
+            load(
+                conn.try_clone().unwrap(),
+                &req,
+                &LoadConfig::default(),
+                &|event| event.objects_read < objects_read,
+            )
+        };
+
+        let loaded = append(vec![logs[0].uri()], u64::MAX).unwrap();
+        assert_eq!(loaded.row_count, 1);
+        assert_eq!(count(&conn, "stdin_logs"), 1);
+        let files = dir.files();
+
+        let all_uris = logs.iter().map(|logs| logs.uri()).collect::<Vec<String>>();
+        let cancelled = append(all_uris.clone(), 1);
+        assert!(matches!(cancelled, Err(LoadError::Cancelled(_))));
+        assert_eq!(dir.files(), files);
+        assert_eq!(count(&conn, "stdin_logs"), 1);
+
+        let loaded = append(all_uris, u64::MAX).unwrap();
+        assert_eq!(loaded.object_count, 2);
+        assert_eq!(count(&conn, "stdin_logs"), 3);
+    }
+
+    fn sampled_load(
+        conn: &Connection,
+        uris: Vec<String>,
+        sample: SampleOptions,
+    ) -> (Loaded, Vec<f64>, Option<String>) {
+        let req = CreateTableRequest {
+            uris,
+            sample: Some(sample),
+            ..request(TableType::Alb, false)
+        };
+        let loaded = load(
+            conn.try_clone().unwrap(),
+            &req,
+            &LoadConfig::default(),
+            &|_| true,
+        )
+        .unwrap();
+        let weights = match conn.prepare("SELECT sample_weight FROM stdin_logs") {
+            Ok(mut stmt) => stmt
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<duckdb::Result<Vec<f64>>>()
+                .unwrap(),
+            Err(_) => Vec::new(),
+        };
+        let entries = catalog::list(conn).unwrap();
+        let sample = TableInfo::from(entries.into_iter().next().unwrap()).sample;
+
+        (
+            loaded,
+            weights,
+            Some(sample).filter(|sample| !sample.is_empty()),
+        )
+    }
+
+    #[test]
+    fn objects_are_sampled_with_their_weight() {
+        let conn = connection();
+        let logs = [
+            LogFile::create("sample-objects-a", &[ALB_LOG_LINE, ALB_LOG_LINE]),
+            LogFile::create("sample-objects-b", &[ALB_LOG_LINE, ALB_LOG_LINE]),
+            LogFile::create("sample-objects-c", &[ALB_LOG_LINE, ALB_LOG_LINE]),
+            LogFile::create("sample-objects-d", &[ALB_LOG_LINE, ALB_LOG_LINE]),
+        ];
+        let uris = vec![logs[0]
+            .uri()
+            .replace("sample-objects-a", "sample-objects-*")];
+
+        let (loaded, weights, sample) = sampled_load(
+            &conn,
+            uris,
+            SampleOptions {
+                percent: 50.0,
+                weight: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!((loaded.object_count, loaded.row_count), (2, 4));
+        assert_eq!(weights, [2.0; 4]);
+        assert_eq!(sample.as_deref(), Some("50% of objects"));
+    }
+
+    #[test]
+    fn rows_are_sampled_by_percentage_or_number() {
+        let logs = LogFile::create("sample-rows", &[ALB_LOG_LINE; 10]);
+        let by_rows = |percent: f64, rows: u64, weight: bool| SampleOptions {
+            percent,
+            rows,
+            method: SampleMethod::Rows.into(),
+            weight,
+        };
+
+        let (loaded, weights, sample) =
+            sampled_load(&connection(), vec![logs.uri()], by_rows(100.0, 0, true));
+        assert_eq!((loaded.object_count, loaded.row_count), (1, 10));
+        assert_eq!(weights, [1.0; 10]);
+        assert_eq!(sample.as_deref(), Some("100% of rows"));
+
+        let (loaded, weights, sample) =
+            sampled_load(&connection(), vec![logs.uri()], by_rows(0.0, 3, false));
+        assert_eq!(loaded.row_count, 3);
+        assert!(weights.is_empty());
+        assert_eq!(sample.as_deref(), Some("3 rows"));
+    }
+
+    #[test]
+    fn invalid_samples_are_refused() {
+        let by = |method: SampleMethod, percent: f64, rows: u64, weight: bool| SampleOptions {
+            percent,
+            rows,
+            method: method.into(),
+            weight,
+        };
+
+        for sample in [
+            by(SampleMethod::Objects, 0.0, 0, false),
+            by(SampleMethod::Objects, 101.0, 0, false),
+            by(SampleMethod::Objects, 0.0, 10, false),
+            by(SampleMethod::Rows, 10.0, 10, false),
+            by(SampleMethod::Rows, 0.0, 10, true),
+        ] {
+            assert!(describe_sample(&sample).is_err(), "{:?}", sample);
+        }
+    }
+
+    #[test]
+    fn record_replaces_entries_of_reloaded_table() {
+        let conn = connection();
+        let entry = || {
+            CatalogEntry::builder()
+                .table_name("alb_logs".to_string())
+                .table_type("alb".to_string())
+                .uris(vec!["s3://bucket/AWSLogs/".to_string()])
+                .row_count(1)
+                .byte_count(1)
+                .duration(Duration::ZERO)
+                .build()
+        };
+        let object = IngestedObject::builder()
+            .uri("s3://bucket/AWSLogs/a.log.gz".to_string())
+            .build();
+
+        record(&conn, &entry(), &[object], false).unwrap();
+        record(&conn, &entry(), &[], false).unwrap();
+
+        assert_eq!(catalog::list(&conn).unwrap().len(), 1);
+        assert!(catalog::ingested_objects(&conn, "alb_logs")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn table_dropped_while_appending_is_not_recorded_again() {
+        let conn = connection();
+        let logs = LogFile::create("upload-dropped", &[ALB_LOG_LINE]);
+        upload(&conn, &request(TableType::Alb, false), &logs).unwrap();
+
+        let drop_conn = std::sync::Mutex::new(conn.try_clone().unwrap());
+        let source = LoadSource::builder()
+            .uris(vec![logs.uri()])
+            .load_uris(vec![logs.uri()])
+            .sizes(vec![0])
+            .byte_count(0)
+            .build();
+        let result = ingest(
+            conn.try_clone().unwrap(),
+            &request(TableType::Alb, true),
+            source,
+            None,
+            Instant::now(),
+            &|progress| {
+                if progress.objects_read == progress.object_count {
+                    let mut drop_conn = drop_conn.lock().unwrap();
+                    let _ = table::drop_tables(&mut drop_conn, &["stdin_logs".to_string()]);
+                }
+                true
+            },
+        );
+
+        let tables = table::list(&conn).unwrap();
+        let entries = catalog::list(&conn).unwrap();
+        assert_eq!(
+            result.is_ok(),
+            tables.contains(&"stdin_logs".to_string()),
+            "{:?}",
+            result
+        );
+        assert_eq!(entries.len(), tables.len());
+    }
+}
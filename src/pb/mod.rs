@@ -1,19 +1,48 @@
+use crate::pb::db::create_table_progress::Event;
+use crate::pb::db::create_table_stream_request::Payload;
 use crate::pb::db::management_server::Management;
 use crate::pb::db::operation_server::Operation;
 use crate::pb::db::{
-    CreateTableReply, CreateTableRequest, HealthCheckReply, HealthCheckRequest, RawQueryReply,
-    RawQueryRequest, ShutdownReply, ShutdownRequest,
+    CancelJobReply, CancelJobRequest, CreateTableProgress, CreateTableReply, CreateTableRequest,
+    CreateTableStreamRequest, CreateViewReply, CreateViewRequest, DescribeTableReply,
+    DescribeTableRequest, DropTablesReply, DropTablesRequest, DropViewsReply, DropViewsRequest,
+    GetJobRequest, HealthCheckReply, HealthCheckRequest, JobInfo, ListJobsReply, ListJobsRequest,
+    ListTablesReply, ListTablesRequest, ListViewsReply, ListViewsRequest, LoadSnapshotRequest,
+    PlanLoadReply, RawQueryReply, RawQueryRequest, SaveSnapshotRequest, ShutdownReply,
+    ShutdownRequest, SnapshotReply, StartFollowRequest, StartJobReply, TableType,
+};
+use crate::pb::load::{
+    create_secrets, ingested_objects, load, load_message, load_upload, source_uris, LoadError,
 };
 use crate::repo;
-use crate::repo::{alb, s3, Client};
+use crate::repo::object::{Object, ObjectSummary};
+use crate::repo::view::ViewEntry;
+use crate::repo::{alb, catalog, object, s3, snapshot, table, view, Client, Sample, Sources};
+use crate::util::cancel::Cancellation;
+use crate::util::config::LoadConfig;
+use crate::util::format;
+use crate::util::job::{JobSpec, Jobs};
+use crate::util::upload::StreamUpload;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, Mutex, RwLock};
-use tonic::{Request, Response, Status};
+use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{debug, info};
 use typed_builder::TypedBuilder;
 
+mod load;
+
+// the stream payload embeds the whole request, which is only sent once per stream
+#[allow(clippy::large_enum_variant)]
 pub(crate) mod db {
     tonic::include_proto!("db");
 }
 
+const PROGRESS_CHANNEL_SIZE: usize = 16;
+
 #[derive(Debug, TypedBuilder)]
 pub(crate) struct ManagementImpl {
     shutdown_tx: mpsc::Sender<()>,
@@ -49,8 +78,11 @@ impl Management for ManagementImpl {
 #[derive(Debug, TypedBuilder)]
 pub(crate) struct OperationImpl {
     db_conn: Mutex<duckdb::Connection>,
+    load_config: LoadConfig,
     #[builder(default)]
     init: RwLock<bool>,
+    #[builder(default)]
+    jobs: Jobs,
 }
 
 impl OperationImpl {
@@ -60,6 +92,51 @@ impl OperationImpl {
             Err(e) => Err(Box::new(e)),
         }
     }
+
+    async fn get_initialized_connection(&self) -> Result<duckdb::Connection, Status> {
+        let conn = self
+            .get_connection()
+            .await
+            .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
+
+        if !*self.init.read().await {
+            repo::init(&conn)
+                .map_err(|e| Status::internal(format!("failed to initialize: {}", e)))?;
+
+            let mut init = self.init.write().await;
+            *init = true;
+        }
+
+        Ok(conn)
+    }
+}
+
+fn new_client(
+    table_type: i32,
+    provenance: bool,
+    sample: Sample,
+    sources: Sources,
+    conn: duckdb::Connection,
+) -> Result<Box<dyn Client>, String> {
+    match table_type {
+        0 => Ok(Box::new(
+            alb::ClientImpl::builder()
+                .conn(conn)
+                .provenance(provenance)
+                .sample(sample)
+                .sources(sources)
+                .build(),
+        )),
+        1 => Ok(Box::new(
+            s3::ClientImpl::builder()
+                .conn(conn)
+                .provenance(provenance)
+                .sample(sample)
+                .sources(sources)
+                .build(),
+        )),
+        _ => Err("invalid table type".into()),
+    }
 }
 
 #[tonic::async_trait]
@@ -70,47 +147,614 @@ impl Operation for OperationImpl {
     ) -> Result<Response<CreateTableReply>, Status> {
         let req = request.into_inner();
 
+        let conn = self.get_initialized_connection().await?;
+        let load_config = self.load_config.clone();
+        let cancellation = Cancellation::new(&conn);
+        let progress_cancellation = cancellation.clone();
+        let reply = cancellation
+            .run_blocking(move || {
+                load(conn, &req, &load_config, &|_| {
+                    !progress_cancellation.is_cancelled()
+                })
+                .map(|loaded| CreateTableReply {
+                    message: load_message(&req, &loaded),
+                    ..loaded.into()
+                })
+            })
+            .await
+            .map_err(|e| Status::internal(format!("{}", e)))??;
+
+        Ok(Response::new(reply))
+    }
+
+    type CreateTableWithProgressStream = ReceiverStream<Result<CreateTableProgress, Status>>;
+
+    async fn create_table_with_progress(
+        &self,
+        request: Request<CreateTableRequest>,
+    ) -> Result<Response<Self::CreateTableWithProgressStream>, Status> {
+        let req = request.into_inner();
+
+        let conn = self.get_initialized_connection().await?;
+        let load_config = self.load_config.clone();
+        let (tx, rx) = mpsc::channel(PROGRESS_CHANNEL_SIZE);
+        let cancellation = Cancellation::new(&conn);
+        let closed_tx = tx.clone();
+        cancellation.cancel_on(async move { closed_tx.closed().await });
+        tokio::spawn(async move {
+            cancellation
+                .run_blocking(move || {
+                    // sends fail once the client has gone away, which stops the load
+                    let result = load(conn, &req, &load_config, &|progress| {
+                        tx.blocking_send(Ok(CreateTableProgress {
+                            event: Some(Event::Progress(progress)),
+                        }))
+                        .is_ok()
+                    });
+                    if let Err(LoadError::Cancelled(message)) = &result {
+                        info!("{}", message);
+                    }
+
+                    let event = result
+                        .map_err(Status::from)
+                        .map(|loaded| CreateTableProgress {
+                            event: Some(Event::Reply(CreateTableReply {
+                                message: load_message(&req, &loaded),
+                                ..loaded.into()
+                            })),
+                        });
+                    let _ = tx.blocking_send(event);
+                })
+                .await
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn raw_query(
+        &self,
+        request: Request<RawQueryRequest>,
+    ) -> Result<Response<RawQueryReply>, Status> {
+        let req = request.into_inner();
+
         let conn = self
             .get_connection()
             .await
             .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
 
-        if !*self.init.read().await {
-            repo::init(&conn)
-                .map_err(|e| Status::internal(format!("failed to initialize: {}", e)))?;
+        let cancellation = Cancellation::new(&conn);
+        let result = cancellation
+            .run_blocking(move || repo::raw_query(&conn, &req.query).map_err(|e| format!("{}", e)))
+            .await
+            .map_err(|e| Status::internal(format!("{}", e)))?
+            .map_err(Status::internal)?;
 
-            let mut init = self.init.write().await;
-            *init = true;
+        Ok(Response::new(result.into()))
+    }
+
+    async fn plan_load(
+        &self,
+        request: Request<CreateTableRequest>,
+    ) -> Result<Response<PlanLoadReply>, Status> {
+        let req = request.into_inner();
+        let uris = source_uris(&req).map_err(Status::invalid_argument)?;
+
+        let conn = self.get_initialized_connection().await?;
+        let _secrets = create_secrets(&conn, &uris, &req)
+            .map_err(|e| Status::internal(format!("failed to create secret: {}", e)))?;
+
+        let (http_uris, uris): (Vec<String>, Vec<String>) =
+            uris.into_iter().partition(|uri| object::is_http(uri));
+
+        let mut summary = if uris.is_empty() {
+            ObjectSummary::default()
+        } else if req.append {
+            let ingested = ingested_objects(&conn, &req)
+                .map_err(|e| Status::internal(format!("failed to get ingested objects: {}", e)))?;
+            let objects = object::list(&conn, &uris)
+                .map_err(|e| Status::internal(format!("failed to list objects: {}", e)))?
+                .into_iter()
+                .filter(|object| !ingested.contains(&object.into()))
+                .collect::<Vec<Object>>();
+            ObjectSummary::from(objects.as_slice())
+        } else {
+            object::summarize(&conn, &uris)
+                .map_err(|e| Status::internal(format!("failed to list objects: {}", e)))?
+        };
+        summary.add_unlisted(http_uris.len() as u64);
+
+        Ok(Response::new(summary.into()))
+    }
+
+    async fn create_table_from_stream(
+        &self,
+        request: Request<Streaming<CreateTableStreamRequest>>,
+    ) -> Result<Response<CreateTableReply>, Status> {
+        let started = Instant::now();
+        let mut stream = request.into_inner();
+
+        let req = match stream.message().await? {
+            Some(CreateTableStreamRequest {
+                payload: Some(Payload::Request(req)),
+            }) => req,
+            _ => return Err(Status::invalid_argument("first message must be a request")),
+        };
+
+        if TableType::try_from(req.table_type).is_err() {
+            return Err(Status::invalid_argument("invalid table type"));
         }
+        if !req.materialize_dir.is_empty() {
+            return Err(Status::invalid_argument(
+                "stdin cannot be materialized as Parquet",
+            ));
+        }
+
+        let mut upload: Option<(StreamUpload, File)> = None;
+        let mut total_bytes = 0;
+        while let Some(message) = stream.message().await? {
+            let Some(Payload::Chunk(chunk)) = message.payload else {
+                return Err(Status::invalid_argument(
+                    "subsequent messages must be chunks",
+                ));
+            };
+
+            total_bytes += chunk.len() as u64;
+            if !req.force && total_bytes > self.load_config.max_bytes {
+                return Err(Status::failed_precondition(format!(
+                    "received more than {}, which exceeds the limit (use --force to load anyway)",
+                    format::bytes(self.load_config.max_bytes)
+                )));
+            }
 
-        let client: Box<dyn Client> = match req.table_type {
-            0 => Box::new(alb::ClientImpl::builder().conn(conn).build()),
-            1 => Box::new(s3::ClientImpl::builder().conn(conn).build()),
-            _ => return Err(Status::invalid_argument("invalid table type")),
+            let (_, file) = match upload.as_mut() {
+                Some(upload) => upload,
+                None => upload.insert(StreamUpload::create(&chunk).map_err(|e| {
+                    Status::internal(format!("failed to create temporary file: {}", e))
+                })?),
+            };
+            file.write_all(&chunk)
+                .map_err(|e| Status::internal(format!("failed to write temporary file: {}", e)))?;
+        }
+
+        let Some((upload, file)) = upload else {
+            return Err(Status::invalid_argument("no data received"));
         };
+        drop(file);
+        debug!(
+            "received {} bytes into {}",
+            total_bytes,
+            upload.path_string()
+        );
+
+        let conn = self.get_initialized_connection().await?;
+        let cancellation = Cancellation::new(&conn);
+        let progress_cancellation = cancellation.clone();
+        let reply = cancellation
+            .run_blocking(move || {
+                // the upload is removed once loaded
+                let loaded = load_upload(
+                    conn,
+                    &req,
+                    upload.path_string(),
+                    total_bytes,
+                    started,
+                    &|_| !progress_cancellation.is_cancelled(),
+                );
+                drop(upload);
+                loaded.map(|loaded| CreateTableReply {
+                    message: load_message(&req, &loaded),
+                    ..loaded.into()
+                })
+            })
+            .await
+            .map_err(|e| Status::internal(format!("{}", e)))??;
+
+        Ok(Response::new(reply))
+    }
+
+    async fn list_tables(
+        &self,
+        _request: Request<ListTablesRequest>,
+    ) -> Result<Response<ListTablesReply>, Status> {
+        let conn = self
+            .get_connection()
+            .await
+            .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
 
-        match client.create_table(&req.table_name, &req.s3_uri) {
-            Ok(_) => Ok(Response::new(CreateTableReply {
-                message: "OK".into(),
-            })),
-            Err(e) => Err(Status::internal(format!("failed to create table: {}", e))),
+        let entries = catalog::list(&conn)
+            .map_err(|e| Status::internal(format!("failed to list tables: {}", e)))?;
+
+        Ok(Response::new(ListTablesReply {
+            tables: entries.into_iter().map(|entry| entry.into()).collect(),
+        }))
+    }
+
+    async fn describe_table(
+        &self,
+        request: Request<DescribeTableRequest>,
+    ) -> Result<Response<DescribeTableReply>, Status> {
+        let req = request.into_inner();
+
+        let conn = self
+            .get_connection()
+            .await
+            .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
+
+        let mut columns = table::summarize(&conn, &req.table_name)
+            .map_err(|e| Status::internal(format!("failed to describe table: {}", e)))?;
+
+        let table_type = catalog::table_type(&conn, &req.table_name)
+            .map_err(|e| Status::internal(format!("failed to get table type: {}", e)))?
+            .and_then(|table_type| TableType::from_str_name(&table_type.to_uppercase()));
+        if let Some(table_type) = table_type {
+            let client = new_client(
+                table_type.into(),
+                false,
+                Sample::default(),
+                Sources::default(),
+                conn,
+            )
+            .map_err(Status::invalid_argument)?;
+            for column in columns.iter_mut() {
+                column.describe(client.column_descriptions());
+            }
         }
+
+        Ok(Response::new(DescribeTableReply {
+            columns: columns.into_iter().map(|column| column.into()).collect(),
+        }))
     }
 
-    async fn raw_query(
+    async fn drop_tables(
         &self,
-        request: Request<RawQueryRequest>,
-    ) -> Result<Response<RawQueryReply>, Status> {
+        request: Request<DropTablesRequest>,
+    ) -> Result<Response<DropTablesReply>, Status> {
+        let req = request.into_inner();
+
+        let mut conn = self
+            .get_connection()
+            .await
+            .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
+
+        let table_names = if req.all {
+            table::list(&conn)
+                .map_err(|e| Status::internal(format!("failed to list tables: {}", e)))?
+        } else if req.table_names.is_empty() {
+            return Err(Status::invalid_argument("table name is required"));
+        } else {
+            req.table_names
+        };
+        if let Some(name) = table_names
+            .iter()
+            .find(|name| catalog::INTERNAL_TABLES.contains(&name.as_str()))
+        {
+            return Err(Status::invalid_argument(format!(
+                "{} cannot be dropped",
+                name
+            )));
+        }
+
+        let view_names = table::dependent_views(&mut conn, &table_names)
+            .map_err(|e| Status::internal(format!("failed to get dependent views: {}", e)))?;
+        if !view_names.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "views {} depend on the tables, drop them first",
+                view_names.join(", ")
+            )));
+        }
+
+        // jobs are cancelled first so that they don't start another load of the tables
+        for table_name in table_names.iter() {
+            let job_ids = self.jobs.cancel_table(table_name).await;
+            if !job_ids.is_empty() {
+                debug!("cancelled jobs {:?} of {}", job_ids, table_name);
+            }
+        }
+        table::drop_tables(&mut conn, &table_names)
+            .map_err(|e| Status::internal(format!("failed to drop tables: {}", e)))?;
+
+        Ok(Response::new(DropTablesReply { table_names }))
+    }
+
+    async fn start_follow(
+        &self,
+        request: Request<StartFollowRequest>,
+    ) -> Result<Response<StartJobReply>, Status> {
+        let req = request.into_inner();
+        let Some(mut load_req) = req.request else {
+            return Err(Status::invalid_argument("request is required"));
+        };
+        if req.interval_secs == 0 {
+            return Err(Status::invalid_argument("interval must be at least 1s"));
+        }
+        let uris = source_uris(&load_req).map_err(Status::invalid_argument)?;
+        load_req.append = true;
+
+        let conn = self.get_initialized_connection().await?;
+        let load_config = self.load_config.clone();
+        let interval = Duration::from_secs(req.interval_secs);
+        let spec = JobSpec::builder()
+            .kind("follow")
+            .table_name(load_req.table_name.clone())
+            .uris(uris)
+            .interval(Some(interval))
+            .build();
+
+        let job_id = self
+            .jobs
+            .spawn(spec, move |status| {
+                load::follow(conn, load_req, load_config, interval, status)
+            })
+            .await;
+
+        Ok(Response::new(StartJobReply { job_id }))
+    }
+
+    async fn start_load(
+        &self,
+        request: Request<CreateTableRequest>,
+    ) -> Result<Response<StartJobReply>, Status> {
+        let req = request.into_inner();
+        let uris = source_uris(&req).map_err(Status::invalid_argument)?;
+
+        let conn = self.get_initialized_connection().await?;
+        let load_config = self.load_config.clone();
+        let spec = JobSpec::builder()
+            .kind("load")
+            .table_name(req.table_name.clone())
+            .uris(uris)
+            .build();
+
+        let job_id = self
+            .jobs
+            .spawn(spec, move |status| {
+                load::detach(conn, req, load_config, status)
+            })
+            .await;
+
+        Ok(Response::new(StartJobReply { job_id }))
+    }
+
+    async fn list_jobs(
+        &self,
+        _request: Request<ListJobsRequest>,
+    ) -> Result<Response<ListJobsReply>, Status> {
+        Ok(Response::new(ListJobsReply {
+            jobs: self.jobs.list().await,
+        }))
+    }
+
+    async fn get_job(&self, request: Request<GetJobRequest>) -> Result<Response<JobInfo>, Status> {
         let req = request.into_inner();
 
+        match self.jobs.get(req.job_id).await {
+            Some(info) => Ok(Response::new(info)),
+            None => Err(Status::not_found(format!("job {} not found", req.job_id))),
+        }
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<CancelJobRequest>,
+    ) -> Result<Response<CancelJobReply>, Status> {
+        let req = request.into_inner();
+
+        if !self.jobs.cancel(req.job_id).await {
+            return Err(Status::not_found(format!("job {} not found", req.job_id)));
+        }
+
+        Ok(Response::new(CancelJobReply {
+            message: "OK".into(),
+        }))
+    }
+
+    async fn save_snapshot(
+        &self,
+        request: Request<SaveSnapshotRequest>,
+    ) -> Result<Response<SnapshotReply>, Status> {
+        let req = request.into_inner();
+        let dir = PathBuf::from(req.dir);
+        if !dir.is_absolute() {
+            return Err(Status::invalid_argument(
+                "snapshot directory must be absolute",
+            ));
+        }
+        if snapshot::exists(&dir) {
+            return Err(Status::failed_precondition(format!(
+                "{:?} already contains a snapshot",
+                dir
+            )));
+        }
+
+        let mut conn = self
+            .get_connection()
+            .await
+            .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
+
+        let manifest = snapshot::save(&mut conn, &dir)
+            .map_err(|e| Status::internal(format!("failed to save snapshot: {}", e)))?;
+
+        Ok(Response::new(SnapshotReply {
+            table_names: manifest.table_names(),
+        }))
+    }
+
+    async fn load_snapshot(
+        &self,
+        request: Request<LoadSnapshotRequest>,
+    ) -> Result<Response<SnapshotReply>, Status> {
+        let req = request.into_inner();
+        let dir = PathBuf::from(req.dir);
+        if !dir.is_absolute() {
+            return Err(Status::invalid_argument(
+                "snapshot directory must be absolute",
+            ));
+        }
+
+        let manifest = snapshot::read_manifest(&dir)
+            .map_err(|e| Status::invalid_argument(format!("invalid snapshot: {}", e)))?;
+
+        let mut conn = self
+            .get_connection()
+            .await
+            .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
+
+        let table_names = manifest.table_names();
+        let existing = table::list(&conn)
+            .map_err(|e| Status::internal(format!("failed to list tables: {}", e)))?;
+        if let Some(name) = table_names.iter().find(|name| existing.contains(name)) {
+            return Err(Status::already_exists(format!(
+                "{} already exists, drop it before loading the snapshot",
+                name
+            )));
+        }
+
+        snapshot::restore(&mut conn, &dir, &manifest)
+            .map_err(|e| Status::internal(format!("failed to load snapshot: {}", e)))?;
+
+        Ok(Response::new(SnapshotReply { table_names }))
+    }
+
+    async fn create_view(
+        &self,
+        request: Request<CreateViewRequest>,
+    ) -> Result<Response<CreateViewReply>, Status> {
+        let req = request.into_inner();
+        if req.view_name.is_empty() || req.sql.is_empty() {
+            return Err(Status::invalid_argument("view name and sql are required"));
+        }
+
+        let mut conn = self
+            .get_connection()
+            .await
+            .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
+
+        let entry = ViewEntry::builder()
+            .view_name(req.view_name)
+            .sql(req.sql)
+            .build();
+        view::create(&mut conn, &entry, req.replace)
+            .map_err(|e| Status::invalid_argument(format!("failed to create view: {}", e)))?;
+
+        Ok(Response::new(CreateViewReply {}))
+    }
+
+    async fn list_views(
+        &self,
+        _request: Request<ListViewsRequest>,
+    ) -> Result<Response<ListViewsReply>, Status> {
         let conn = self
             .get_connection()
             .await
             .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
 
-        let result =
-            repo::raw_query(&conn, &req.query).map_err(|e| Status::internal(format!("{}", e)))?;
+        let entries = view::list(&conn)
+            .map_err(|e| Status::internal(format!("failed to list views: {}", e)))?;
 
-        Ok(Response::new(result.into()))
+        Ok(Response::new(ListViewsReply {
+            views: entries.into_iter().map(|entry| entry.into()).collect(),
+        }))
+    }
+
+    async fn drop_views(
+        &self,
+        request: Request<DropViewsRequest>,
+    ) -> Result<Response<DropViewsReply>, Status> {
+        let req = request.into_inner();
+        if req.view_names.is_empty() {
+            return Err(Status::invalid_argument("view name is required"));
+        }
+
+        let mut conn = self
+            .get_connection()
+            .await
+            .map_err(|e| Status::internal(format!("failed to get connection: {}", e)))?;
+
+        view::drop_views(&mut conn, &req.view_names)
+            .map_err(|e| Status::internal(format!("failed to drop views: {}", e)))?;
+
+        Ok(Response::new(DropViewsReply {
+            view_names: req.view_names,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::tests::{connection, LogFile, ALB_LOG_LINE};
+
+    fn operation(load_config: LoadConfig) -> OperationImpl {
+        // repo::init installs httpfs, which local files don't need
+        OperationImpl::builder()
+            .db_conn(Mutex::new(connection()))
+            .load_config(load_config)
+            .init(RwLock::new(true))
+            .build()
+    }
+
+    fn alb_request(uris: Vec<String>) -> CreateTableRequest {
+        CreateTableRequest {
+            table_type: TableType::Alb.into(),
+            table_name: "alb_logs".to_string(),
+            uris,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn plan_load_summarizes_matching_objects_without_loading() {
+        let a = LogFile::create("plan-a", &[ALB_LOG_LINE]);
+        let _b = LogFile::create("plan-b", &[ALB_LOG_LINE, ALB_LOG_LINE]);
+        let operation = operation(LoadConfig::default());
+
+        let reply = operation
+            .plan_load(Request::new(alb_request(vec![
+                a.uri().replace("plan-a", "plan-*"),
+                "https://example.com/presigned.log?X-Amz-Signature=abc".to_string(),
+            ])))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(reply.object_count, 3);
+        assert_eq!(reply.total_bytes, (ALB_LOG_LINE.len() as u64 + 1) * 3,);
+        assert!(!reply.first_modified.is_empty());
+        let conn = operation.get_connection().await.unwrap();
+        assert!(table::list(&conn).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_tables_adds_up_appended_loads() {
+        let a = LogFile::create("list-a", &[ALB_LOG_LINE]);
+        let b = LogFile::create("list-b", &[ALB_LOG_LINE, ALB_LOG_LINE]);
+        let operation = operation(LoadConfig::default());
+        let create_table = |req: CreateTableRequest| operation.create_table(Request::new(req));
+
+        create_table(alb_request(vec![a.uri()])).await.unwrap();
+        create_table(CreateTableRequest {
+            append: true,
+            ..alb_request(vec![a.uri(), b.uri()])
+        })
+        .await
+        .unwrap();
+        let tables = operation
+            .list_tables(Request::new(ListTablesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .tables;
+
+        assert_eq!(tables.len(), 1);
+        let table = &tables[0];
+        assert_eq!(
+            (table.table_name.as_str(), table.table_type.as_str()),
+            ("alb_logs", "alb")
+        );
+        assert_eq!(table.uris, [a.uri(), b.uri()]);
+        assert_eq!(table.row_count, 3);
+        assert_eq!(table.byte_count, (ALB_LOG_LINE.len() as u64 + 1) * 3);
+        assert!(!table.loaded_at.is_empty());
+        assert!(table.location.is_empty() && table.sample.is_empty());
     }
 }
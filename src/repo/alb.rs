@@ -1,67 +1,405 @@
-use crate::repo::Client;
+use crate::repo::{
+    compression, line_number_column, sql_list, store_rejects_options, Client, Sample, Sources,
+};
 use duckdb::Connection;
 use typed_builder::TypedBuilder;
 
+const AWS_LOGS_DIR: &str = "AWSLogs/";
+const ALB_SERVICE_DIR: &str = "elasticloadbalancing";
+const ALL: &str = "*";
+
+pub(crate) fn expand_uri(
+    uri: &str,
+    accounts: &[String],
+    regions: &[String],
+) -> Result<Vec<String>, String> {
+    if accounts.is_empty() && regions.is_empty() {
+        return Ok(vec![uri.to_string()]);
+    }
+
+    // s3://bucket/prefix/AWSLogs/<account>/elasticloadbalancing/<region>/<rest>
+    let (base, account, region, rest) = match uri.find(AWS_LOGS_DIR) {
+        Some(pos) => {
+            let (base, path) = uri.split_at(pos + AWS_LOGS_DIR.len());
+            let parts = path.splitn(4, '/').collect::<Vec<&str>>();
+            if parts.len() < 3
+                || parts[1] != ALB_SERVICE_DIR
+                || parts[0].is_empty()
+                || parts[2].is_empty()
+            {
+                return Err(format!(
+                    "uri does not follow the ALB access log layout: {}",
+                    uri
+                ));
+            }
+            (
+                base.to_string(),
+                parts[0],
+                parts[2],
+                parts.get(3).copied().unwrap_or("**/*.log.gz"),
+            )
+        }
+        None if uri.contains(['*', '?', '[', '{']) => {
+            return Err(format!(
+                "uri must be a prefix without wildcards, or contain AWSLogs/: {}",
+                uri
+            ));
+        }
+        None => (
+            format!("{}/{}", uri.trim_end_matches('/'), AWS_LOGS_DIR),
+            ALL,
+            ALL,
+            "**/*.log.gz",
+        ),
+    };
+
+    let accounts = expand_values(accounts, account)?;
+    let regions = expand_values(regions, region)?;
+
+    let mut uris = Vec::new();
+    for account in accounts.iter() {
+        for region in regions.iter() {
+            uris.push(format!(
+                "{}{}/{}/{}/{}",
+                base, account, ALB_SERVICE_DIR, region, rest
+            ));
+        }
+    }
+
+    Ok(uris)
+}
+
+// Repeated values are loaded once, as each of them is a glob of its own.
+fn expand_values(values: &[String], default: &str) -> Result<Vec<String>, String> {
+    if values.is_empty() {
+        return Ok(vec![default.to_string()]);
+    }
+    if values.iter().any(|v| v.is_empty()) {
+        return Err("accounts and regions must not be empty".into());
+    }
+    if values.iter().any(|v| v == ALL) {
+        return Ok(vec![ALL.to_string()]);
+    }
+
+    let mut expanded = Vec::new();
+    for value in values.iter() {
+        if !expanded.contains(value) {
+            expanded.push(value.clone());
+        }
+    }
+
+    Ok(expanded)
+}
+
+// https://docs.aws.amazon.com/elasticloadbalancing/latest/application/load-balancer-access-logs.html
+const COLUMN_DESCRIPTIONS: &[(&str, &str)] = &[
+    ("type", "request or connection type: http, https, h2, grpcs, ws or wss"),
+    ("time", "time the load balancer generated the response to the client (UTC), or closed the connection for websockets"),
+    ("elb", "resource ID of the load balancer"),
+    ("client_port", "client IP address and port"),
+    ("target_port", "target IP address and port, `-` if the request was not forwarded to a target"),
+    ("request_processing_time", "seconds from receiving the request until sending it to a target, -1 if it was not dispatched to a target or the connection was closed"),
+    ("target_processing_time", "seconds from sending the request to a target until the target started to respond, -1 if it was not dispatched to a target or the target closed the connection"),
+    ("response_processing_time", "seconds from receiving the response headers from the target until sending the response to the client, -1 if it was not dispatched to a target or the connection was closed"),
+    ("elb_status_code", "status code of the response from the load balancer"),
+    ("target_status_code", "status code of the response from the target, NULL if the target did not respond (`-` in the log)"),
+    ("received_bytes", "size of the request received from the client, including headers"),
+    ("sent_bytes", "size of the response sent to the client, including headers"),
+    ("request", "request line from the client: method, protocol://host:port/uri and HTTP version"),
+    ("user_agent", "User-Agent header of the request"),
+    ("ssl_cipher", "TLS cipher of https listeners, `-` otherwise"),
+    ("ssl_protocol", "TLS protocol of https listeners, `-` otherwise"),
+    ("target_group_arn", "ARN of the target group"),
+    ("trace_id", "contents of the X-Amzn-Trace-Id header"),
+    ("domain_name", "SNI domain sent by the client in the TLS handshake, `-` otherwise"),
+    ("chosen_cert_arn", "ARN of the certificate presented to the client, `session-reused` if the session was reused"),
+    ("matched_rule_priority", "priority of the listener rule that matched the request, 0 for the default rule, `-` if no rule matched"),
+    ("request_creation_time", "time the load balancer received the request from the client (UTC)"),
+    ("actions_executed", "comma separated actions taken for the request e.g. waf,forward"),
+    ("redirect_url", "Location header of redirect responses, `-` otherwise"),
+    ("error_reason", "error reason code e.g. TargetConnectionErrorCode, `-` if there was no error"),
+    ("target_port_list", "space separated IP addresses and ports of the targets that processed the request"),
+    ("target_status_code_list", "space separated status codes of the target responses"),
+    ("classification", "desync mitigation classification: Acceptable, Ambiguous or Severe"),
+    ("classification_reason", "desync mitigation classification reason code"),
+    ("conn_trace_id", "traceability ID linking the request to the connection logs"),
+    ("account_id", "AWS account ID taken from the object path"),
+    ("region", "AWS region taken from the object path"),
+    ("source_file", "uri of the log object the row was read from (--provenance)"),
+    ("line_number", "line number of the row in the log object (--provenance)"),
+    ("lb_name", "name of the load balancer taken from the object path (--provenance)"),
+    ("sample_weight", "number of rows the row stands for in a sampled load (--sample-weight)"),
+];
+
 #[derive(TypedBuilder)]
 pub(crate) struct ClientImpl {
     conn: Connection,
+    #[builder(default)]
+    provenance: bool,
+    #[builder(default)]
+    sample: Sample,
+    #[builder(default)]
+    sources: Sources,
 }
 
 impl Client for ClientImpl {
-    fn create_table(&self, table_name: &str, s3_uri: &str) -> duckdb::Result<usize> {
-        self.conn.execute(
-            format!(
-                r#"CREATE TABLE {} AS
-                WITH csv_logs AS (
-                    SELECT * FROM read_csv(
-                        '{}',
-                        columns={{
-                            'type': 'VARCHAR',
-                            'time': 'TIMESTAMP',
-                            'elb': 'VARCHAR',
-                            'client_port': 'VARCHAR',
-                            'target_port': 'VARCHAR',
-                            'request_processing_time': 'DOUBLE',
-                            'target_processing_time': 'DOUBLE',
-                            'response_processing_time': 'DOUBLE',
-                            'elb_status_code': 'INTEGER',
-                            'target_status_code': 'VARCHAR',
-                            'received_bytes': 'BIGINT',
-                            'sent_bytes': 'BIGINT',
-                            'request': 'VARCHAR',
-                            'user_agent': 'VARCHAR',
-                            'ssl_cipher': 'VARCHAR',
-                            'ssl_protocol': 'VARCHAR',
-                            'target_group_arn': 'VARCHAR',
-                            'trace_id': 'VARCHAR',
-                            'domain_name': 'VARCHAR',
-                            'chosen_cert_arn': 'VARCHAR',
-                            'matched_rule_priority': 'VARCHAR',
-                            'request_creation_time': 'TIMESTAMP',
-                            'actions_executed': 'VARCHAR',
-                            'redirect_url': 'VARCHAR',
-                            'error_reason': 'VARCHAR',
-                            'target_port_list': 'VARCHAR',
-                            'target_status_code_list': 'VARCHAR',
-                            'classification': 'VARCHAR',
-                            'classification_reason': 'VARCHAR',
-                            'conn_trace_id': 'VARCHAR'
-                        }},
-                        delim=' ',
-                        quote='"',
-                        escape='"',
-                        header=False,
-                        auto_detect=False
-                    )
+    fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    fn sample(&self) -> &Sample {
+        &self.sample
+    }
+
+    fn sources(&self) -> &Sources {
+        &self.sources
+    }
+
+    fn select_query(&self, uris: &[String]) -> String {
+        format!(
+            r#"WITH csv_logs AS (
+                SELECT * FROM read_csv(
+                    {},
+                    columns={{
+                        'type': 'VARCHAR',
+                        'time': 'TIMESTAMP',
+                        'elb': 'VARCHAR',
+                        'client_port': 'VARCHAR',
+                        'target_port': 'VARCHAR',
+                        'request_processing_time': 'DOUBLE',
+                        'target_processing_time': 'DOUBLE',
+                        'response_processing_time': 'DOUBLE',
+                        'elb_status_code': 'INTEGER',
+                        'target_status_code': 'VARCHAR',
+                        'received_bytes': 'BIGINT',
+                        'sent_bytes': 'BIGINT',
+                        'request': 'VARCHAR',
+                        'user_agent': 'VARCHAR',
+                        'ssl_cipher': 'VARCHAR',
+                        'ssl_protocol': 'VARCHAR',
+                        'target_group_arn': 'VARCHAR',
+                        'trace_id': 'VARCHAR',
+                        'domain_name': 'VARCHAR',
+                        'chosen_cert_arn': 'VARCHAR',
+                        'matched_rule_priority': 'VARCHAR',
+                        'request_creation_time': 'TIMESTAMP',
+                        'actions_executed': 'VARCHAR',
+                        'redirect_url': 'VARCHAR',
+                        'error_reason': 'VARCHAR',
+                        'target_port_list': 'VARCHAR',
+                        'target_status_code_list': 'VARCHAR',
+                        'classification': 'VARCHAR',
+                        'classification_reason': 'VARCHAR',
+                        'conn_trace_id': 'VARCHAR'
+                    }},
+                    delim=' ',
+                    quote='"',
+                    escape='"',
+                    header=False,
+                    auto_detect=False,
+                    compression='{}',
+                    filename=True,
+                    {}
                 )
-                SELECT
-                    csv_logs.* exclude (target_status_code),
-                    try_cast(csv_logs.target_status_code AS INTEGER) AS target_status_code
-                FROM csv_logs;"#,
-                table_name, s3_uri
             )
-            .as_str(),
-            [],
+            SELECT
+                csv_logs.* exclude (target_status_code, filename, source_file{row_id}),
+                try_cast(csv_logs.target_status_code AS INTEGER) AS target_status_code,
+                nullif(regexp_extract(csv_logs.source_file, 'AWSLogs/([0-9]+)/elasticloadbalancing/', 1), '') AS account_id,
+                nullif(regexp_extract(csv_logs.source_file, 'AWSLogs/[0-9]+/elasticloadbalancing/([^/]+)/', 1), '') AS region{provenance}
+            FROM (SELECT *, {source_file} AS source_file FROM {source}) AS csv_logs"#,
+            sql_list(uris),
+            compression(uris),
+            store_rejects_options(),
+            row_id = if self.provenance { ", row_id" } else { "" },
+            provenance = if self.provenance {
+                format!(
+                    ",\ncsv_logs.source_file,\n{},\n{}",
+                    line_number_column("csv_logs.filename", "csv_logs.row_id"),
+                    // <account>_elasticloadbalancing_<region>_app.<name>.<id>_<time>_<ip>_<random>.log.gz
+                    "nullif(regexp_extract(csv_logs.source_file, '_elasticloadbalancing_[^_/]+_[a-z]+\\.([A-Za-z0-9-]+)\\.[^_/]+_[^/]*$', 1), '') AS lb_name"
+                )
+            } else {
+                String::new()
+            },
+            source_file = self.sources.source_file("filename", uris),
+            source = if self.provenance {
+                "(SELECT *, row_number() OVER () AS row_id FROM csv_logs)"
+            } else {
+                "csv_logs"
+            }
         )
     }
+
+    fn column_descriptions(&self) -> &'static [(&'static str, &'static str)] {
+        COLUMN_DESCRIPTIONS
+    }
+
+    fn time_expression(&self) -> &'static str {
+        "time"
+    }
+
+    fn time_column(&self) -> &'static str {
+        "time"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::tests::{connection, LogFile, ALB_LOG_LINE};
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn expand_uri_without_accounts_and_regions() {
+        assert_eq!(
+            expand_uri("s3://bucket/prefix", &[], &[]),
+            Ok(strings(&["s3://bucket/prefix"]))
+        );
+    }
+
+    #[test]
+    fn expand_uri_from_bucket() {
+        assert_eq!(
+            expand_uri(
+                "s3://bucket/prefix/",
+                &strings(&["111111111111", "222222222222"]),
+                &strings(&["us-east-1"])
+            ),
+            Ok(strings(&[
+                "s3://bucket/prefix/AWSLogs/111111111111/elasticloadbalancing/us-east-1/**/*.log.gz",
+                "s3://bucket/prefix/AWSLogs/222222222222/elasticloadbalancing/us-east-1/**/*.log.gz",
+            ]))
+        );
+    }
+
+    #[test]
+    fn expand_uri_keeps_rest_of_path() {
+        assert_eq!(
+            expand_uri(
+                "s3://bucket/AWSLogs/111111111111/elasticloadbalancing/ap-northeast-1/2024/01/*",
+                &[],
+                &strings(&["us-east-1", "*"])
+            ),
+            Ok(strings(&[
+                "s3://bucket/AWSLogs/111111111111/elasticloadbalancing/*/2024/01/*",
+            ]))
+        );
+    }
+
+    #[test]
+    fn expand_uri_dedupes_values() {
+        assert_eq!(
+            expand_uri(
+                "s3://bucket",
+                &strings(&["111111111111", "111111111111"]),
+                &strings(&["us-east-1", "us-west-2", "us-east-1"])
+            ),
+            Ok(strings(&[
+                "s3://bucket/AWSLogs/111111111111/elasticloadbalancing/us-east-1/**/*.log.gz",
+                "s3://bucket/AWSLogs/111111111111/elasticloadbalancing/us-west-2/**/*.log.gz",
+            ]))
+        );
+    }
+
+    #[test]
+    fn expand_uri_rejects_empty_segments() {
+        let accounts = strings(&["111111111111"]);
+        assert!(expand_uri(
+            "s3://bucket/AWSLogs/111111111111/elasticloadbalancing/",
+            &accounts,
+            &[]
+        )
+        .is_err());
+        assert!(expand_uri(
+            "s3://bucket/AWSLogs//elasticloadbalancing/us-east-1/",
+            &[],
+            &strings(&["us-east-1"])
+        )
+        .is_err());
+        assert!(expand_uri("s3://bucket", &strings(&[""]), &[]).is_err());
+    }
+
+    #[test]
+    fn expand_uri_rejects_other_layouts() {
+        assert!(expand_uri(
+            "s3://bucket/AWSLogs/111111111111/cloudtrail/us-east-1/",
+            &strings(&["111111111111"]),
+            &[]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn expand_uri_rejects_wildcards_without_aws_logs() {
+        for uri in ["s3://bucket/prefix/**/*.log.gz", "s3://bucket/prefix-?/"] {
+            assert!(expand_uri(uri, &strings(&["111111111111"]), &[]).is_err());
+        }
+    }
+
+    #[test]
+    fn rows_of_local_copies_are_attributed_to_their_sources() {
+        let logs = LogFile::create("alb-sources", &[ALB_LOG_LINE]);
+        let uri = "s3://bucket/AWSLogs/123456789012/elasticloadbalancing/ap-northeast-1/2024/01/01/123456789012_elasticloadbalancing_ap-northeast-1_app.my-lb.0123456789abcdef_20240101T0000Z_192.0.2.1_abc.log.gz";
+        let mut sources = Sources::default();
+        sources.insert(logs.uri(), uri.to_string());
+        let client = ClientImpl::builder()
+            .conn(connection())
+            .provenance(true)
+            .sources(sources)
+            .build();
+        client.create_table("alb_logs", &[logs.uri()]).unwrap();
+
+        let row: (String, String, String, String) = client
+            .conn()
+            .query_row(
+                "SELECT source_file, account_id, region, lb_name FROM alb_logs",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (
+                uri.to_string(),
+                "123456789012".to_string(),
+                "ap-northeast-1".to_string(),
+                "my-lb".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn line_numbers_skip_rejected_lines() {
+        let logs = LogFile::create(
+            "alb-rejects",
+            &[ALB_LOG_LINE, "not an alb log line", ALB_LOG_LINE],
+        );
+        let client = ClientImpl::builder()
+            .conn(connection())
+            .provenance(true)
+            .build();
+        client.create_table("alb_logs", &[logs.uri()]).unwrap();
+        let reject_count = client
+            .store_rejects("alb_logs", &[logs.uri()], false, true)
+            .unwrap();
+
+        let line_numbers = |query: &str| {
+            let mut stmt = client.conn().prepare(query).unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.collect::<duckdb::Result<Vec<i64>>>().unwrap()
+        };
+        assert_eq!(reject_count, 1);
+        assert_eq!(
+            line_numbers("SELECT line_number FROM alb_logs ORDER BY line_number"),
+            [1, 3]
+        );
+        assert_eq!(
+            line_numbers("SELECT line_number FROM alb_logs_rejects"),
+            [2]
+        );
+    }
 }
@@ -0,0 +1,289 @@
+use crate::pb::db::TableInfo;
+use crate::repo::sql_list;
+use chrono::Utc;
+use duckdb::{params, Connection};
+use std::collections::HashSet;
+use std::time::Duration;
+use typed_builder::TypedBuilder;
+
+pub(crate) const CATALOG_TABLE: &str = "lupe_catalog";
+pub(crate) const OBJECTS_TABLE: &str = "lupe_objects";
+pub(crate) const VIEWS_TABLE: &str = "lupe_views";
+pub(crate) const INTERNAL_TABLES: [&str; 3] = [CATALOG_TABLE, OBJECTS_TABLE, VIEWS_TABLE];
+
+pub(crate) fn init(conn: &Connection) -> duckdb::Result<()> {
+    conn.execute_batch(
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                table_name VARCHAR,
+                table_type VARCHAR,
+                uris VARCHAR[],
+                row_count UBIGINT,
+                byte_count UBIGINT,
+                duration_ms UBIGINT,
+                loaded_at TIMESTAMP,
+                location VARCHAR,
+                sample VARCHAR
+            );
+            CREATE TABLE IF NOT EXISTS {} (
+                table_name VARCHAR,
+                uri VARCHAR,
+                size UBIGINT,
+                last_modified_ms BIGINT
+            );
+            CREATE TABLE IF NOT EXISTS {} (
+                view_name VARCHAR,
+                sql VARCHAR,
+                created_at TIMESTAMP
+            );",
+            CATALOG_TABLE, OBJECTS_TABLE, VIEWS_TABLE
+        )
+        .as_str(),
+    )
+}
+
+#[derive(Debug, TypedBuilder)]
+pub(crate) struct CatalogEntry {
+    table_name: String,
+    table_type: String,
+    uris: Vec<String>,
+    row_count: u64,
+    byte_count: u64,
+    duration: Duration,
+    #[builder(default = Utc::now().naive_utc().to_string())]
+    loaded_at: String,
+    // directory of the Parquet files of materialized tables
+    #[builder(default)]
+    location: Option<String>,
+    // how the latest load was sampled
+    #[builder(default)]
+    sample: Option<String>,
+}
+
+impl CatalogEntry {
+    pub(crate) fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub(crate) fn byte_count(&self) -> u64 {
+        self.byte_count
+    }
+}
+
+pub(crate) fn insert(conn: &Connection, entry: &CatalogEntry) -> duckdb::Result<usize> {
+    conn.execute(
+        format!(
+            "INSERT INTO {} (table_name, table_type, uris, row_count, byte_count, duration_ms, loaded_at, location, sample)
+            VALUES (?, ?, {}, ?, ?, ?, CAST(? AS TIMESTAMP), ?, ?)",
+            CATALOG_TABLE,
+            sql_list(&entry.uris)
+        )
+        .as_str(),
+        params![
+            entry.table_name,
+            entry.table_type,
+            entry.row_count,
+            entry.byte_count,
+            entry.duration.as_millis() as u64,
+            entry.loaded_at,
+            entry.location,
+            entry.sample,
+        ],
+    )
+}
+
+// Appended loads add up to the entry of the table, which is inserted if the table
+// was not loaded by lupe.
+pub(crate) fn append(conn: &Connection, entry: &CatalogEntry) -> duckdb::Result<usize> {
+    let updated = conn.execute(
+        format!(
+            "UPDATE {} SET
+                uris = list_concat(uris, list_filter({}, uri -> NOT list_contains(uris, uri))),
+                row_count = row_count + ?,
+                byte_count = byte_count + ?,
+                duration_ms = duration_ms + ?,
+                loaded_at = CAST(? AS TIMESTAMP),
+                sample = ?
+            WHERE table_name = ?",
+            CATALOG_TABLE,
+            sql_list(&entry.uris)
+        )
+        .as_str(),
+        params![
+            entry.row_count,
+            entry.byte_count,
+            entry.duration.as_millis() as u64,
+            entry.loaded_at,
+            entry.sample,
+            entry.table_name,
+        ],
+    )?;
+
+    if updated == 0 {
+        insert(conn, entry)
+    } else {
+        Ok(updated)
+    }
+}
+
+pub(crate) fn list(conn: &Connection) -> duckdb::Result<Vec<CatalogEntry>> {
+    // uris are read one per line, so they never contain a newline
+    let mut stmt = conn.prepare(
+        format!(
+            "SELECT
+                table_name,
+                table_type,
+                array_to_string(uris, chr(10)),
+                row_count,
+                byte_count,
+                duration_ms,
+                CAST(loaded_at AS VARCHAR),
+                location,
+                sample
+            FROM {}
+            ORDER BY loaded_at",
+            CATALOG_TABLE
+        )
+        .as_str(),
+    )?;
+
+    let entries = stmt.query_map([], |row| {
+        let uris: String = row.get(2)?;
+        Ok(CatalogEntry::builder()
+            .table_name(row.get(0)?)
+            .table_type(row.get(1)?)
+            .uris(uris.lines().map(|uri| uri.to_string()).collect())
+            .row_count(row.get(3)?)
+            .byte_count(row.get(4)?)
+            .duration(Duration::from_millis(row.get(5)?))
+            .loaded_at(row.get(6)?)
+            .location(row.get(7)?)
+            .sample(row.get(8)?)
+            .build())
+    })?;
+
+    entries.collect()
+}
+
+pub(crate) fn remove(conn: &Connection, table_name: &str) -> duckdb::Result<()> {
+    for internal_table in [CATALOG_TABLE, OBJECTS_TABLE] {
+        conn.execute(
+            format!("DELETE FROM {} WHERE table_name = ?", internal_table).as_str(),
+            params![table_name],
+        )?;
+    }
+
+    Ok(())
+}
+
+// Objects are identified by their size and last modified time, unless they were not listed.
+#[derive(Debug, PartialEq, Eq, Hash, TypedBuilder)]
+pub(crate) struct IngestedObject {
+    uri: String,
+    #[builder(default)]
+    size: Option<u64>,
+    #[builder(default)]
+    last_modified_ms: Option<i64>,
+}
+
+pub(crate) fn insert_objects(
+    conn: &Connection,
+    table_name: &str,
+    objects: &[IngestedObject],
+) -> duckdb::Result<()> {
+    let mut appender = conn.appender(OBJECTS_TABLE)?;
+    for object in objects.iter() {
+        appender.append_row(params![
+            table_name,
+            object.uri,
+            object.size,
+            object.last_modified_ms
+        ])?;
+    }
+
+    appender.flush()
+}
+
+pub(crate) fn ingested_objects(
+    conn: &Connection,
+    table_name: &str,
+) -> duckdb::Result<HashSet<IngestedObject>> {
+    let mut stmt = conn.prepare(
+        format!(
+            "SELECT uri, size, last_modified_ms FROM {} WHERE table_name = ?",
+            OBJECTS_TABLE
+        )
+        .as_str(),
+    )?;
+
+    let objects = stmt.query_map(params![table_name], |row| {
+        Ok(IngestedObject::builder()
+            .uri(row.get(0)?)
+            .size(row.get(1)?)
+            .last_modified_ms(row.get(2)?)
+            .build())
+    })?;
+
+    objects.collect()
+}
+
+impl From<CatalogEntry> for TableInfo {
+    fn from(value: CatalogEntry) -> Self {
+        Self {
+            table_name: value.table_name,
+            table_type: value.table_type,
+            uris: value.uris,
+            row_count: value.row_count,
+            byte_count: value.byte_count,
+            duration_ms: value.duration.as_millis() as u64,
+            loaded_at: value.loaded_at,
+            location: value.location.unwrap_or_default(),
+            sample: value.sample.unwrap_or_default(),
+        }
+    }
+}
+
+// the type of the latest load, or None for tables created by raw queries
+pub(crate) fn table_type(conn: &Connection, table_name: &str) -> duckdb::Result<Option<String>> {
+    let mut stmt = conn.prepare(
+        format!(
+            "SELECT table_type FROM {} WHERE table_name = ? ORDER BY loaded_at DESC LIMIT 1",
+            CATALOG_TABLE
+        )
+        .as_str(),
+    )?;
+
+    let mut rows = stmt.query(params![table_name])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn location(conn: &Connection, table_name: &str) -> duckdb::Result<Option<String>> {
+    let mut stmt = conn.prepare(
+        format!(
+            "SELECT location FROM {} WHERE table_name = ? AND location IS NOT NULL LIMIT 1",
+            CATALOG_TABLE
+        )
+        .as_str(),
+    )?;
+
+    let mut rows = stmt.query(params![table_name])?;
+    match rows.next()? {
+        Some(row) => row.get(0),
+        None => Ok(None),
+    }
+}
+
+// Tables restored from snapshots are regular tables, even if they were materialized.
+pub(crate) fn clear_location(conn: &Connection, table_name: &str) -> duckdb::Result<usize> {
+    conn.execute(
+        format!(
+            "UPDATE {} SET location = NULL WHERE table_name = ?",
+            CATALOG_TABLE
+        )
+        .as_str(),
+        params![table_name],
+    )
+}
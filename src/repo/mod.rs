@@ -16,17 +16,18 @@ use std::sync::Arc;
 use typed_builder::TypedBuilder;
 
 pub(crate) mod alb;
+pub(crate) mod catalog;
+pub(crate) mod object;
 pub(crate) mod s3;
+pub(crate) mod secret;
+pub(crate) mod snapshot;
+pub(crate) mod table;
+pub(crate) mod view;
 
 pub(crate) fn init(conn: &Connection) -> duckdb::Result<()> {
     conn.execute_batch(
         "INSTALL httpfs;
-        LOAD httpfs;
-        CREATE SECRET (
-            TYPE S3,
-            PROVIDER CREDENTIAL_CHAIN,
-            CHAIN 'config;sts;sso;env'
-        );",
+        LOAD httpfs;",
     )
 }
 
@@ -51,8 +52,341 @@ pub(crate) fn raw_query(
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RowSample {
+    Percent(f64),
+    Rows(u64),
+}
+
+#[derive(Debug, Default, TypedBuilder)]
+pub(crate) struct Sample {
+    #[builder(default)]
+    rows: Option<RowSample>,
+    // number of rows each loaded row stands for, added as the sample_weight column
+    #[builder(default)]
+    weight: Option<f64>,
+}
+
+impl Sample {
+    pub(crate) fn rows(&self) -> Option<RowSample> {
+        self.rows
+    }
+
+    fn query(&self, query: String) -> String {
+        if self.rows.is_none() && self.weight.is_none() {
+            return query;
+        }
+
+        format!(
+            "SELECT *{} FROM ({}){}",
+            match self.weight {
+                Some(weight) => format!(", CAST({} AS DOUBLE) AS sample_weight", weight),
+                None => String::new(),
+            },
+            query,
+            match self.rows {
+                Some(RowSample::Percent(percent)) =>
+                    format!(" USING SAMPLE {} PERCENT (bernoulli)", percent),
+                Some(RowSample::Rows(rows)) => format!(" USING SAMPLE {} ROWS", rows),
+                None => String::new(),
+            }
+        )
+    }
+}
+
+// Uris of the local copies a load reads, such as cached objects, which the rows read from
+// them are attributed to.
+#[derive(Debug, Default)]
+pub(crate) struct Sources(HashMap<String, String>);
+
+impl Sources {
+    pub(crate) fn insert(&mut self, load_uri: String, uri: String) {
+        self.0.insert(load_uri, uri);
+    }
+
+    // uri of the rows read from `filename`, one of `uris`
+    fn source_file(&self, filename: &str, uris: &[String]) -> String {
+        let sources = uris
+            .iter()
+            .filter_map(|load_uri| {
+                let uri = self.0.get(load_uri)?;
+                Some(format!("({}, {})", sql_string(load_uri), sql_string(uri)))
+            })
+            .collect::<Vec<String>>();
+        if sources.is_empty() {
+            return filename.to_string();
+        }
+
+        format!(
+            "coalesce((SELECT uri FROM (VALUES {}) AS sources(load_uri, uri) WHERE load_uri = {filename}), {filename})",
+            sources.join(", ")
+        )
+    }
+}
+
 pub(crate) trait Client {
-    fn create_table(&self, table_name: &str, s3_uri: &str) -> duckdb::Result<usize>;
+    fn conn(&self) -> &Connection;
+    fn sample(&self) -> &Sample;
+    fn sources(&self) -> &Sources;
+    fn select_query(&self, uris: &[String]) -> String;
+    fn column_descriptions(&self) -> &'static [(&'static str, &'static str)];
+    // expression of the request time in the rows of `select_query`
+    fn time_expression(&self) -> &'static str;
+    // column of the request time in tables
+    fn time_column(&self) -> &'static str;
+
+    fn time_range(&self, table_name: &str) -> duckdb::Result<(Option<String>, Option<String>)> {
+        self.conn().query_row(
+            format!(
+                "SELECT CAST(min({time}) AS VARCHAR), CAST(max({time}) AS VARCHAR) FROM {};",
+                table_name,
+                time = self.time_column()
+            )
+            .as_str(),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+
+    fn sampled_query(&self, uris: &[String]) -> String {
+        self.sample().query(self.select_query(uris))
+    }
+
+    // Prepares the rows `select_query` reads from, before each statement loading `uris`.
+    fn stage(&self, _uris: &[String]) -> duckdb::Result<()> {
+        Ok(())
+    }
+
+    // drops what `stage` left for `rejects_query`
+    fn drop_staged(&self) -> duckdb::Result<()> {
+        Ok(())
+    }
+
+    // lines of `uris` which were left out of `select_query`, with their source_file,
+    // line_number, reason and line
+    fn rejects_query(&self, uris: &[String]) -> String {
+        csv_rejects_query(self.sources(), uris)
+    }
+
+    // Stores the lines rejected by the previous load into `<table>_rejects`, which is
+    // recreated unless appending, and returns their number.
+    // With `renumber`, line numbers are corrected past the lines read_csv skipped.
+    fn store_rejects(
+        &self,
+        table_name: &str,
+        uris: &[String],
+        append: bool,
+        renumber: bool,
+    ) -> duckdb::Result<usize> {
+        let rejects_table = rejects_table_name(table_name);
+        if !append {
+            self.conn()
+                .execute_batch(format!("DROP TABLE IF EXISTS {};", rejects_table).as_str())?;
+        }
+        self.conn().execute_batch(
+            format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    source_file VARCHAR,
+                    line_number BIGINT,
+                    reason VARCHAR,
+                    line VARCHAR
+                );",
+                rejects_table
+            )
+            .as_str(),
+        )?;
+        let reject_count = self.conn().execute(
+            format!(
+                "INSERT INTO {} BY NAME {};",
+                rejects_table,
+                self.rejects_query(uris)
+            )
+            .as_str(),
+            [],
+        )?;
+
+        if renumber && reject_count > 0 {
+            self.conn().execute(
+                format!(
+                    "UPDATE {table} SET line_number = line_number + (
+                        SELECT count(*) FROM (
+                            SELECT
+                                source_file,
+                                line_number - row_number() OVER (PARTITION BY source_file ORDER BY line_number) AS loaded_before
+                            FROM ({})
+                        ) AS rejects
+                        WHERE rejects.source_file = {table}.source_file
+                            AND rejects.loaded_before < {table}.line_number
+                    );",
+                    csv_rejects_query(self.sources(), uris),
+                    table = table_name
+                )
+                .as_str(),
+                [],
+            )?;
+        }
+
+        self.conn().execute_batch(
+            format!(
+                "DROP TABLE IF EXISTS {};
+                DROP TABLE IF EXISTS {};",
+                REJECT_ERRORS_TABLE, REJECT_SCANS_TABLE
+            )
+            .as_str(),
+        )?;
+        self.drop_staged()?;
+
+        Ok(reject_count)
+    }
+
+    fn create_table(&self, table_name: &str, uris: &[String]) -> duckdb::Result<usize> {
+        self.stage(uris)?;
+        self.conn().execute(
+            format!(
+                "CREATE TABLE {} AS {};",
+                table_name,
+                self.sampled_query(uris)
+            )
+            .as_str(),
+            [],
+        )?;
+
+        // CREATE TABLE AS does not report the number of rows
+        self.conn().query_row(
+            format!("SELECT count(*) FROM {};", table_name).as_str(),
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    fn insert_into(&self, table_name: &str, uris: &[String]) -> duckdb::Result<usize> {
+        self.stage(uris)?;
+        self.conn().execute(
+            format!(
+                "INSERT INTO {} BY NAME {};",
+                table_name,
+                self.sampled_query(uris)
+            )
+            .as_str(),
+            [],
+        )
+    }
+
+    // Writes the rows as Parquet files partitioned by the UTC date and hour of the request, and
+    // creates the table as a view over them so that filters on date and hour prune files.
+    fn materialize(
+        &self,
+        table_name: &str,
+        uris: &[String],
+        dir: &str,
+        append: bool,
+    ) -> duckdb::Result<usize> {
+        self.stage(uris)?;
+        let row_count = self.conn().execute(
+            format!(
+                "COPY (
+                    SELECT
+                        *,
+                        CAST(CAST({time} AS TIMESTAMP) AS DATE) AS date,
+                        hour(CAST({time} AS TIMESTAMP)) AS hour
+                    FROM ({})
+                ) TO {} (FORMAT PARQUET, PARTITION_BY (date, hour){});",
+                self.sampled_query(uris),
+                sql_string(dir),
+                if append { ", APPEND" } else { "" },
+                time = self.time_expression()
+            )
+            .as_str(),
+            [],
+        )?;
+
+        // the view lists the files when queried, so appended files need no new view
+        if !append {
+            self.conn().execute(
+                format!(
+                    "CREATE VIEW {} AS FROM read_parquet({}, hive_partitioning = true);",
+                    table_name,
+                    sql_string(&format!("{}/**/*.parquet", dir.trim_end_matches('/')))
+                )
+                .as_str(),
+                [],
+            )?;
+        }
+
+        Ok(row_count)
+    }
+}
+
+// DuckDB detects the compression from the file extension, which does not work for
+// urls with a query string such as pre-signed urls.
+fn compression(uris: &[String]) -> &'static str {
+    let gzip = uris.iter().all(|uri| {
+        uri.split('?')
+            .next()
+            .is_some_and(|path| path.ends_with(".gz"))
+    });
+
+    if gzip {
+        "gzip"
+    } else {
+        "auto"
+    }
+}
+
+// Rows are numbered in the order they are read, which follows the lines of each object as
+// DuckDB preserves the insertion order.
+fn line_number_column(filename: &str, row_id: &str) -> String {
+    format!("{row_id} - min({row_id}) OVER (PARTITION BY {filename}) + 1 AS line_number")
+}
+
+const REJECT_ERRORS_TABLE: &str = "lupe_reject_errors";
+const REJECT_SCANS_TABLE: &str = "lupe_reject_scans";
+
+pub(crate) fn rejects_table_name(table_name: &str) -> String {
+    format!("{}_rejects", table_name)
+}
+
+// read_csv skips the lines it cannot read and stores them in temporary tables of the
+// connection, which are dropped by `Client::store_rejects`.
+fn store_rejects_options() -> String {
+    format!(
+        "store_rejects=True,\nrejects_table={},\nrejects_scan={}",
+        sql_string(REJECT_ERRORS_TABLE),
+        sql_string(REJECT_SCANS_TABLE)
+    )
+}
+
+// One row per rejected line, with the error of its first column as a line may have errors
+// in several columns, e.g. a short line has one for each missing column.
+fn csv_rejects_query(sources: &Sources, uris: &[String]) -> String {
+    format!(
+        "SELECT {} AS source_file, line_number, reason, line
+        FROM (
+            SELECT
+                scans.file_path,
+                errors.line AS line_number,
+                arg_min(errors.error_message, errors.column_idx) AS reason,
+                any_value(errors.csv_line) AS line
+            FROM {} AS errors JOIN {} AS scans USING (scan_id, file_id)
+            GROUP BY ALL
+        )",
+        sources.source_file("file_path", uris),
+        REJECT_ERRORS_TABLE,
+        REJECT_SCANS_TABLE
+    )
+}
+
+fn sql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn sql_list(values: &[String]) -> String {
+    let values = values
+        .iter()
+        .map(|v| sql_string(v))
+        .collect::<Vec<String>>();
+
+    format!("[{}]", values.join(", "))
 }
 
 #[derive(TypedBuilder)]
@@ -632,3 +966,72 @@ impl TryFrom<&Arc<dyn Array>> for Values {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // https://docs.aws.amazon.com/AmazonS3/latest/userguide/LogFormat.html
+    pub(crate) const S3_LOG_LINE: &str = r#"79a59df900b949e55d96a1e698fbacedfd6e09d98eacf8f8d5218e7cd47ef2be awsexamplebucket1 [06/Feb/2019:00:00:38 +0000] 192.0.2.3 79a59df900b949e55d96a1e698fbacedfd6e09d98eacf8f8d5218e7cd47ef2be 3E57427F3EXAMPLE REST.GET.VERSIONING - "GET /awsexamplebucket1?versioning HTTP/1.1" 200 - 113 - 7 - "-" "S3Console/0.4" - s9lzHYrFp76ZVxRcpX9+5cjAnEH2ROuNkd2BHfIa6UkFVdtjf5mKR3/eTPFvsiP/XV/VLi31234= SigV4 ECDHE-RSA-AES128-GCM-SHA256 AuthHeader awsexamplebucket1.s3.us-west-1.amazonaws.com TLSV1.2 arn:aws:s3:us-west-1:123456789012:accesspoint/example-AP Yes"#;
+
+    // https://docs.aws.amazon.com/elasticloadbalancing/latest/application/load-balancer-access-logs.html
+    pub(crate) const ALB_LOG_LINE: &str = r#"http 2018-07-02T22:23:00.186641Z app/my-loadbalancer/50dc6c495c0c9188 192.168.131.39:2817 10.0.0.1:80 0.000 0.001 0.000 200 200 34 366 "GET http://www.example.com:80/ HTTP/1.1" "curl/7.46.0" - - arn:aws:elasticloadbalancing:us-east-2:123456789012:targetgroup/my-targets/73e2d6bc24d8a067 "Root=1-58337262-36d228ad5d99923122bbe354" "-" "-" 0 2018-07-02T22:22:48.364000Z "forward" "-" "-" "10.0.0.1:80" "200" "-" "-" TID_1234abcd5678ef90"#;
+
+    pub(crate) fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        catalog::init(&conn).unwrap();
+
+        conn
+    }
+
+    // Log file removed on drop.
+    pub(crate) struct LogFile(PathBuf);
+
+    impl LogFile {
+        pub(crate) fn create(name: &str, lines: &[&str]) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("lupe-test-{}-{}.log", std::process::id(), name));
+            std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+            Self(path)
+        }
+
+        pub(crate) fn uri(&self) -> String {
+            self.0.to_string_lossy().to_string()
+        }
+    }
+
+    impl Drop for LogFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn uris(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn compression_of_presigned_urls() {
+        assert_eq!(
+            compression(&uris(&[
+                "https://bucket.s3.amazonaws.com/a.log.gz?X-Amz-Signature=abc",
+                "s3://bucket/b.log.gz",
+            ])),
+            "gzip"
+        );
+    }
+
+    #[test]
+    fn compression_detected_unless_all_gzip() {
+        assert_eq!(
+            compression(&uris(&["s3://bucket/a.log.gz", "s3://bucket/b.log"])),
+            "auto"
+        );
+        assert_eq!(
+            compression(&uris(&["https://example.com/a.log?name=b.gz"])),
+            "auto"
+        );
+    }
+}
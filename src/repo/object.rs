@@ -0,0 +1,230 @@
+use crate::pb::db::PlanLoadReply;
+use crate::repo::catalog::IngestedObject;
+use crate::repo::{sql_list, sql_string};
+use crate::util::cache;
+use crate::util::config::LoadConfig;
+use crate::util::format;
+use chrono::{DateTime, Utc};
+use duckdb::Connection;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+use typed_builder::TypedBuilder;
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub(crate) struct Object {
+    uri: String,
+    size: u64,
+    last_modified: SystemTime,
+}
+
+#[derive(Default, TypedBuilder)]
+pub(crate) struct ObjectSummary {
+    object_count: u64,
+    total_bytes: u64,
+    first_modified: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Object {
+    pub(crate) fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl From<&Object> for IngestedObject {
+    fn from(value: &Object) -> Self {
+        Self::builder()
+            .uri(value.uri.clone())
+            .size(Some(value.size))
+            .last_modified_ms(
+                value
+                    .last_modified
+                    .duration_since(UNIX_EPOCH)
+                    .ok()
+                    .map(|d| d.as_millis() as i64),
+            )
+            .build()
+    }
+}
+
+impl From<&[Object]> for ObjectSummary {
+    fn from(value: &[Object]) -> Self {
+        let to_string = |time: SystemTime| DateTime::<Utc>::from(time).naive_utc().to_string();
+
+        Self::builder()
+            .object_count(value.len() as u64)
+            .total_bytes(value.iter().map(|object| object.size).sum())
+            .first_modified(value.iter().map(|o| o.last_modified).min().map(to_string))
+            .last_modified(value.iter().map(|o| o.last_modified).max().map(to_string))
+            .build()
+    }
+}
+
+impl ObjectSummary {
+    pub(crate) fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    // http(s) objects cannot be listed, so their size is unknown
+    pub(crate) fn add_unlisted(&mut self, count: u64) {
+        self.object_count += count;
+    }
+
+    pub(crate) fn check_limits(&self, config: &LoadConfig) -> Result<(), String> {
+        if self.object_count > config.max_objects {
+            return Err(format!(
+                "matched {} objects, which exceeds the limit of {} objects (use --force to load anyway)",
+                self.object_count, config.max_objects
+            ));
+        }
+        if self.total_bytes > config.max_bytes {
+            return Err(format!(
+                "matched {} of objects, which exceeds the limit of {} (use --force to load anyway)",
+                format::bytes(self.total_bytes),
+                format::bytes(config.max_bytes)
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl From<ObjectSummary> for PlanLoadReply {
+    fn from(value: ObjectSummary) -> Self {
+        Self {
+            object_count: value.object_count,
+            total_bytes: value.total_bytes,
+            first_modified: value.first_modified.unwrap_or_default(),
+            last_modified: value.last_modified.unwrap_or_default(),
+        }
+    }
+}
+
+pub(crate) fn is_s3(uri: &str) -> bool {
+    uri.starts_with("s3://")
+}
+
+pub(crate) fn is_gcs(uri: &str) -> bool {
+    uri.starts_with("gs://") || uri.starts_with("gcs://")
+}
+
+pub(crate) fn is_azure(uri: &str) -> bool {
+    uri.starts_with("az://") || uri.starts_with("azure://") || uri.starts_with("abfss://")
+}
+
+pub(crate) fn is_http(uri: &str) -> bool {
+    uri.starts_with("http://") || uri.starts_with("https://")
+}
+
+pub(crate) fn summarize(conn: &Connection, uris: &[String]) -> duckdb::Result<ObjectSummary> {
+    // read_blob only fetches object metadata unless the content column is selected
+    conn.query_row(
+        format!(
+            r#"SELECT
+                count(*),
+                coalesce(sum(size), 0),
+                CAST(min(last_modified) AS VARCHAR),
+                CAST(max(last_modified) AS VARCHAR)
+            FROM read_blob({});"#,
+            sql_list(uris)
+        )
+        .as_str(),
+        [],
+        |row| {
+            Ok(ObjectSummary::builder()
+                .object_count(row.get(0)?)
+                .total_bytes(row.get(1)?)
+                .first_modified(row.get(2)?)
+                .last_modified(row.get(3)?)
+                .build())
+        },
+    )
+}
+
+pub(crate) fn list(conn: &Connection, uris: &[String]) -> duckdb::Result<Vec<Object>> {
+    let mut stmt = conn.prepare(
+        format!(
+            "SELECT filename, size, epoch_ms(last_modified) FROM read_blob({}) ORDER BY filename;",
+            sql_list(uris)
+        )
+        .as_str(),
+    )?;
+
+    let objects = stmt
+        .query_map([], |row| {
+            Ok(Object::builder()
+                .uri(row.get(0)?)
+                .size(row.get(1)?)
+                .last_modified(UNIX_EPOCH + Duration::from_millis(row.get(2)?))
+                .build())
+        })?
+        .collect::<duckdb::Result<Vec<Object>>>()?;
+
+    Ok(objects)
+}
+
+// local path of the cached copy of the object, unless it cannot be cached
+pub(crate) fn cache_path(object: &Object) -> Option<String> {
+    cache::object_path(&object.uri, object.size, object.last_modified)
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+// Downloads the object to its cached copy at `path`, unless it was fetched before.
+pub(crate) fn fetch(
+    conn: &Connection,
+    object: &Object,
+    path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Path::new(path);
+    if cache::is_cached(path) {
+        debug!("cache hit: {}", object.uri);
+        return Ok(());
+    }
+
+    debug!("cache miss: {}", object.uri);
+    let content: Vec<u8> = conn.query_row(
+        format!(
+            "SELECT content FROM read_blob({});",
+            sql_string(&object.uri)
+        )
+        .as_str(),
+        [],
+        |row| row.get(0),
+    )?;
+    cache::store(path, &content, &object.uri, object.last_modified)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemes() {
+        assert!(is_s3("s3://bucket/key"));
+        assert!(!is_s3("https://bucket.s3.amazonaws.com/key"));
+
+        assert!(is_gcs("gs://bucket/key"));
+        assert!(is_gcs("gcs://bucket/key"));
+        assert!(!is_gcs("s3://bucket/key"));
+
+        assert!(is_azure("az://container/key"));
+        assert!(is_azure("azure://container/key"));
+        assert!(is_azure(
+            "abfss://container@account.dfs.core.windows.net/key"
+        ));
+        assert!(!is_azure("s3://bucket/key"));
+
+        assert!(is_http("http://localhost:8080/key"));
+        assert!(is_http(
+            "https://bucket.s3.amazonaws.com/key?X-Amz-Signature=abc"
+        ));
+        assert!(!is_http("s3://bucket/https://key"));
+    }
+}
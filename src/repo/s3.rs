@@ -1,71 +1,349 @@
-use crate::repo::Client;
+use crate::repo::{
+    compression, csv_rejects_query, line_number_column, sql_list, store_rejects_options, Client,
+    Sample, Sources,
+};
 use duckdb::Connection;
 use typed_builder::TypedBuilder;
 
+// temporary tables of the lines being loaded, and of the lines not matching the log format
+const LINES_TABLE: &str = "lupe_s3_lines";
+const UNMATCHED_TABLE: &str = "lupe_s3_unmatched";
+
+const LOG_PATTERN: &str = r#"^([0-9a-zA-Z]+)\s+([a-z0-9.\-]+)\s+\[([0-9/A-Za-z: +]+)\] ([^ ]+) ([^ ]+) ([^ ]+) ([^ ]+) ([^ ]+) ("[^"]*"|-) ([^ ]+) ([^ ]+) (\d+|-) (\d+|-) (\d+|-) (\d+|-) ("[^"]*"|-) ("[^"]*"|-) (\S+) (\S+) (\S+) (\S+) (\S+) (\S+) (\S+) (\S+) (\S+)(.*)$"#;
+
+// https://docs.aws.amazon.com/AmazonS3/latest/userguide/LogFormat.html
+const COLUMN_DESCRIPTIONS: &[(&str, &str)] = &[
+    (
+        "bucket_owner",
+        "canonical user ID of the owner of the source bucket",
+    ),
+    (
+        "bucket",
+        "name of the bucket the request was processed against",
+    ),
+    (
+        "time",
+        "time the request was received, as written in the log",
+    ),
+    (
+        "time_1",
+        "time the request was received, parsed as a timestamp",
+    ),
+    ("remote_ip", "apparent IP address of the requester"),
+    (
+        "requester",
+        "canonical user ID or IAM ARN of the requester, `-` for unauthenticated requests",
+    ),
+    ("request_id", "request ID generated by S3"),
+    (
+        "operation",
+        "operation e.g. REST.GET.OBJECT, S3.EXPIRE.OBJECT",
+    ),
+    (
+        "key",
+        "object key, `-` if the operation does not take a key",
+    ),
+    ("request_uri", "Request-URI part of the HTTP request"),
+    ("http_status", "HTTP status code of the response"),
+    (
+        "error_code",
+        "S3 error code e.g. NoSuchKey, `-` if there was no error",
+    ),
+    (
+        "bytes_sent",
+        "response bytes sent excluding HTTP overhead, NULL if zero (`-` in the log)",
+    ),
+    ("object_size", "total size of the object, NULL if unknown"),
+    (
+        "total_time",
+        "milliseconds the request was in flight from the server's perspective",
+    ),
+    (
+        "turn_around_time",
+        "milliseconds S3 spent processing the request",
+    ),
+    ("referrer", "Referer header of the request"),
+    ("user_agent", "User-Agent header of the request"),
+    ("version_id", "version ID in the request, `-` otherwise"),
+    ("host_id", "x-amz-id-2 request ID"),
+    (
+        "signature_version",
+        "SigV2 or SigV4, `-` for unauthenticated requests",
+    ),
+    (
+        "cipher_suite",
+        "TLS cipher of https requests, `-` otherwise",
+    ),
+    (
+        "authentication_type",
+        "AuthHeader, QueryString (e.g. pre-signed urls), `-` for unauthenticated requests",
+    ),
+    ("host_header", "endpoint used to connect to S3"),
+    (
+        "tls_version",
+        "TLS version of https requests, `-` otherwise",
+    ),
+    (
+        "access_point_arn",
+        "ARN of the access point of the request, `-` otherwise",
+    ),
+    (
+        "acl_required",
+        "`Yes` if the request required an ACL for authorization, `-` otherwise",
+    ),
+    (
+        "source_file",
+        "uri of the log object the row was read from (--provenance)",
+    ),
+    (
+        "line_number",
+        "line number of the row in the log object (--provenance)",
+    ),
+    (
+        "source_bucket",
+        "bucket of the log object the row was read from (--provenance)",
+    ),
+    (
+        "sample_weight",
+        "number of rows the row stands for in a sampled load (--sample-weight)",
+    ),
+];
+
 #[derive(TypedBuilder)]
 pub(crate) struct ClientImpl {
     conn: Connection,
+    #[builder(default)]
+    provenance: bool,
+    #[builder(default)]
+    sample: Sample,
+    #[builder(default)]
+    sources: Sources,
+}
+
+impl ClientImpl {
+    // one row per line, numbered in the order they are read
+    fn source(&self, uris: &[String]) -> String {
+        format!(
+            r#"(
+                SELECT *, row_number() OVER () AS row_id FROM read_csv(
+                    {},
+                    columns={{
+                        'col1': 'VARCHAR'
+                    }},
+                    delim='\t',
+                    quote='"',
+                    escape='"',
+                    header=False,
+                    auto_detect=False,
+                    compression='{}',
+                    filename=True,
+                    {}
+                )
+            )"#,
+            sql_list(uris),
+            compression(uris),
+            store_rejects_options()
+        )
+    }
 }
 
 impl Client for ClientImpl {
-    fn create_table(&self, table_name: &str, s3_uri: &str) -> duckdb::Result<usize> {
-        self.conn.execute(
+    fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    fn sample(&self) -> &Sample {
+        &self.sample
+    }
+
+    fn sources(&self) -> &Sources {
+        &self.sources
+    }
+
+    // reads the lines staged by `stage`
+    fn select_query(&self, _uris: &[String]) -> String {
+        format!(
+            r#"WITH parsed_logs AS (
+                SELECT
+                    regexp_extract(col1, '{pattern}',
+                    [
+                        'bucket_owner',
+                        'bucket',
+                        'time',
+                        'remote_ip',
+                        'requester',
+                        'request_id',
+                        'operation',
+                        'key',
+                        'request_uri',
+                        'http_status',
+                        'error_code',
+                        'bytes_sent',
+                        'object_size',
+                        'total_time',
+                        'turn_around_time',
+                        'referrer',
+                        'user_agent',
+                        'version_id',
+                        'host_id',
+                        'signature_version',
+                        'cipher_suite',
+                        'authentication_type',
+                        'host_header',
+                        'tls_version',
+                        'access_point_arn',
+                        'acl_required'
+                    ]) AS log_struct,
+                    source_file,
+                    line_number
+                FROM {lines}
+                WHERE matched
+            )
+            SELECT
+                log_struct.* exclude (bytes_sent, object_size, total_time, turn_around_time),
+                strptime(log_struct.time, '%d/%b/%Y:%H:%M:%S %z') AS time_1,
+                try_cast(log_struct.bytes_sent AS INTEGER) AS bytes_sent,
+                try_cast(log_struct.object_size AS INTEGER) AS object_size,
+                try_cast(log_struct.total_time AS INTEGER) AS total_time,
+                try_cast(log_struct.turn_around_time AS INTEGER) AS turn_around_time,{provenance}
+            FROM parsed_logs"#,
+            pattern = LOG_PATTERN,
+            lines = LINES_TABLE,
+            provenance = if self.provenance {
+                "\nsource_file,\nline_number,\nnullif(regexp_extract(source_file, '^[a-z0-9]+://([^/]+)/', 1), '') AS source_bucket"
+            } else {
+                ""
+            }
+        )
+    }
+
+    // Lines are parsed once, so the line numbers of the rows are taken before the lines
+    // not matching the log format are set aside. Lines read_csv skipped are left to its
+    // rejects.
+    fn stage(&self, uris: &[String]) -> duckdb::Result<()> {
+        self.conn().execute_batch(
             format!(
-                r#"CREATE TABLE {} AS
-                WITH parsed_logs AS (
-                    SELECT
-                        regexp_extract(col1, '^([0-9a-zA-Z]+)\s+([a-z0-9.\-]+)\s+\[([0-9/A-Za-z: +]+)\] ([^ ]+) ([^ ]+) ([^ ]+) ([^ ]+) ([^ ]+) ("[^"]*"|-) ([^ ]+) ([^ ]+) (\d+|-) (\d+|-) (\d+|-) (\d+|-) ("[^"]*"|-) ("[^"]*"|-) (\S+) (\S+) (\S+) (\S+) (\S+) (\S+) (\S+) (\S+) (\S+)(.*)$',
-                        [
-                            'bucket_owner',
-                            'bucket',
-                            'time',
-                            'remote_ip',
-                            'requester',
-                            'request_id',
-                            'operation',
-                            'key',
-                            'request_uri',
-                            'http_status',
-                            'error_code',
-                            'bytes_sent',
-                            'object_size',
-                            'total_time',
-                            'turn_around_time',
-                            'referrer',
-                            'user_agent',
-                            'version_id',
-                            'host_id',
-                            'signature_version',
-                            'cipher_suite',
-                            'authentication_type',
-                            'host_header',
-                            'tls_version',
-                            'access_point_arn',
-                            'acl_required'
-                        ]) AS log_struct
-                    FROM read_csv(
-                        '{}',
-                        columns={{
-                            'col1': 'VARCHAR'
-                        }},
-                        delim='\t',
-                        quote='"',
-                        escape='"',
-                        header=False,
-                        auto_detect=False
-                    )
-                )
+                "CREATE OR REPLACE TEMP TABLE {lines} AS
                 SELECT
-                    log_struct.* exclude (bytes_sent, object_size, total_time, turn_around_time),
-                    strptime(log_struct.time, '%d/%b/%Y:%H:%M:%S %z') AS time,
-                    try_cast(log_struct.bytes_sent AS INTEGER) AS bytes_sent,
-                    try_cast(log_struct.object_size AS INTEGER) AS object_size,
-                    try_cast(log_struct.total_time AS INTEGER) AS total_time,
-                    try_cast(log_struct.turn_around_time AS INTEGER) AS turn_around_time,
-                FROM parsed_logs;"#,
-                table_name, s3_uri
-            ).as_str(),
-            []
+                    col1,
+                    regexp_matches(col1, '{pattern}') AS matched,
+                    {source_file} AS source_file,
+                    {line_number}
+                FROM {source};
+                CREATE TEMP TABLE IF NOT EXISTS {unmatched} AS FROM {lines} LIMIT 0;
+                INSERT INTO {unmatched} FROM {lines} WHERE NOT matched;",
+                lines = LINES_TABLE,
+                unmatched = UNMATCHED_TABLE,
+                pattern = LOG_PATTERN,
+                source_file = self.sources.source_file("filename", uris),
+                line_number = line_number_column("filename", "row_id"),
+                source = self.source(uris)
+            )
+            .as_str(),
         )
     }
+
+    fn drop_staged(&self) -> duckdb::Result<()> {
+        self.conn().execute_batch(
+            format!(
+                "DROP TABLE IF EXISTS {};
+                DROP TABLE IF EXISTS {};",
+                LINES_TABLE, UNMATCHED_TABLE
+            )
+            .as_str(),
+        )
+    }
+
+    fn rejects_query(&self, uris: &[String]) -> String {
+        format!(
+            "{}
+            UNION ALL
+            SELECT
+                source_file,
+                line_number,
+                'line does not match the S3 access log format' AS reason,
+                col1 AS line
+            FROM {}",
+            csv_rejects_query(&self.sources, uris),
+            UNMATCHED_TABLE
+        )
+    }
+
+    fn column_descriptions(&self) -> &'static [(&'static str, &'static str)] {
+        COLUMN_DESCRIPTIONS
+    }
+
+    // `time` is the raw field, and `time_1` the parsed one
+    fn time_expression(&self) -> &'static str {
+        "strptime(time, '%d/%b/%Y:%H:%M:%S %z')"
+    }
+
+    fn time_column(&self) -> &'static str {
+        "time_1"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::db::ColumnInfo;
+    use crate::repo::table;
+    use crate::repo::tests::{connection, LogFile, S3_LOG_LINE};
+
+    #[test]
+    fn all_columns_are_described() {
+        let logs = LogFile::create("s3-describe", &[S3_LOG_LINE]);
+        let client = ClientImpl::builder()
+            .conn(connection())
+            .provenance(true)
+            .build();
+        client.create_table("s3_logs", &[logs.uri()]).unwrap();
+
+        let undescribed = table::summarize(client.conn(), "s3_logs")
+            .unwrap()
+            .into_iter()
+            .map(|mut column| {
+                column.describe(client.column_descriptions());
+                ColumnInfo::from(column)
+            })
+            .filter(|column| column.description.is_empty())
+            .map(|column| column.column_name)
+            .collect::<Vec<String>>();
+
+        assert!(undescribed.is_empty(), "{:?}", undescribed);
+    }
+
+    #[test]
+    fn unmatched_lines_are_stored_as_rejects() {
+        let logs = LogFile::create(
+            "s3-rejects",
+            &[S3_LOG_LINE, "not an access log line", S3_LOG_LINE],
+        );
+        let more_logs = LogFile::create("s3-rejects-more", &["not an access log line"]);
+        let client = ClientImpl::builder()
+            .conn(connection())
+            .provenance(true)
+            .build();
+        // loaded in two batches, whose rejects are stored together
+        client.create_table("s3_logs", &[logs.uri()]).unwrap();
+        client.insert_into("s3_logs", &[more_logs.uri()]).unwrap();
+        let reject_count = client
+            .store_rejects("s3_logs", &[logs.uri(), more_logs.uri()], false, true)
+            .unwrap();
+
+        let line_numbers = |query: &str| {
+            let mut stmt = client.conn().prepare(query).unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.collect::<duckdb::Result<Vec<i64>>>().unwrap()
+        };
+        assert_eq!(reject_count, 2);
+        assert_eq!(
+            line_numbers("SELECT line_number FROM s3_logs ORDER BY line_number"),
+            [1, 3]
+        );
+        assert_eq!(
+            line_numbers("SELECT line_number FROM s3_logs_rejects ORDER BY source_file"),
+            [1, 2]
+        );
+    }
 }
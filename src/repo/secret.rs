@@ -0,0 +1,249 @@
+use crate::pb::db::{AzureCredential, GcsCredential};
+use crate::repo::{sql_list, sql_string};
+use duckdb::Connection;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::warn;
+
+static NEXT_SECRET_ID: AtomicU64 = AtomicU64::new(0);
+
+// The secrets of a request, which are dropped with it. Secrets are shared by all the
+// connections of the database, so each is named uniquely and scoped to the uris of its
+// request, which keeps concurrent requests with other options from replacing them.
+pub(crate) struct Secrets {
+    conn: Connection,
+    names: Vec<String>,
+}
+
+impl Secrets {
+    // a connection of its own still drops the secrets when the request's was interrupted
+    pub(crate) fn new(conn: &Connection) -> duckdb::Result<Self> {
+        Ok(Self {
+            conn: conn.try_clone()?,
+            names: vec![],
+        })
+    }
+
+    // Created for every load, which also picks up refreshed credentials of the chain.
+    pub(crate) fn create_s3(
+        &mut self,
+        uris: &[String],
+        requester_pays: bool,
+    ) -> duckdb::Result<()> {
+        let options = vec![
+            "TYPE S3".to_string(),
+            "PROVIDER CREDENTIAL_CHAIN".to_string(),
+            "CHAIN 'config;sts;sso;env'".to_string(),
+            format!("REQUESTER_PAYS {}", requester_pays),
+        ];
+        self.create("s3", options, uris)
+    }
+
+    // GCS is accessed through its S3 compatible API with HMAC keys.
+    pub(crate) fn create_gcs(
+        &mut self,
+        uris: &[String],
+        credential: &GcsCredential,
+    ) -> duckdb::Result<()> {
+        self.create("gcs", gcs_options(credential), uris)
+    }
+
+    pub(crate) fn create_azure(
+        &mut self,
+        uris: &[String],
+        credential: &AzureCredential,
+    ) -> duckdb::Result<()> {
+        self.conn.execute_batch("INSTALL azure; LOAD azure;")?;
+
+        // e.g. the connection string of Azurite
+        let options = if !credential.connection_string.is_empty() {
+            vec![
+                "TYPE AZURE".to_string(),
+                format!(
+                    "CONNECTION_STRING {}",
+                    sql_string(&credential.connection_string)
+                ),
+            ]
+        } else {
+            vec![
+                "TYPE AZURE".to_string(),
+                "PROVIDER CREDENTIAL_CHAIN".to_string(),
+                format!("ACCOUNT_NAME {}", sql_string(&credential.account_name)),
+            ]
+        };
+        self.create("azure", options, uris)
+    }
+
+    fn create(
+        &mut self,
+        kind: &str,
+        mut options: Vec<String>,
+        uris: &[String],
+    ) -> duckdb::Result<()> {
+        let name = format!(
+            "lupe_{}_{}",
+            kind,
+            NEXT_SECRET_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        options.push(format!("SCOPE {}", sql_list(&scope(uris))));
+
+        self.conn
+            .execute_batch(format!("CREATE SECRET {} ({});", name, options.join(", ")).as_str())?;
+        self.names.push(name);
+
+        Ok(())
+    }
+}
+
+impl Drop for Secrets {
+    fn drop(&mut self) {
+        for name in self.names.iter() {
+            let dropped = self
+                .conn
+                .execute_batch(format!("DROP SECRET IF EXISTS {};", name).as_str());
+            if let Err(e) = dropped {
+                warn!("failed to drop secret {}: {}", name, e);
+            }
+        }
+    }
+}
+
+// Secrets are matched by the prefix of a path, so the uris are cut before any wildcard.
+fn scope(uris: &[String]) -> Vec<String> {
+    let mut prefixes: Vec<String> = vec![];
+    for uri in uris {
+        let prefix = match uri.find(['*', '?', '[', '{']) {
+            Some(i) => &uri[..i],
+            None => uri.as_str(),
+        };
+        if !prefixes.iter().any(|p| p == prefix) {
+            prefixes.push(prefix.to_string());
+        }
+    }
+
+    prefixes
+}
+
+fn gcs_options(credential: &GcsCredential) -> Vec<String> {
+    let mut options = vec![
+        "TYPE GCS".to_string(),
+        format!("KEY_ID {}", sql_string(&credential.key_id)),
+        format!("SECRET {}", sql_string(&credential.secret)),
+    ];
+
+    // e.g. http://localhost:4443 for fake-gcs-server
+    if !credential.endpoint.is_empty() {
+        let (endpoint, use_ssl) = match credential.endpoint.split_once("://") {
+            Some((scheme, endpoint)) => (endpoint, scheme != "http"),
+            None => (credential.endpoint.as_str(), true),
+        };
+        options.push(format!("ENDPOINT {}", sql_string(endpoint)));
+        options.push("URL_STYLE 'path'".to_string());
+        options.push(format!("USE_SSL {}", use_ssl));
+    }
+
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credential(endpoint: &str) -> GcsCredential {
+        GcsCredential {
+            key_id: "GOOG1EXAMPLE".to_string(),
+            secret: "it's secret".to_string(),
+            endpoint: endpoint.to_string(),
+        }
+    }
+
+    #[test]
+    fn scope_cuts_uris_before_wildcards() {
+        let uris = [
+            "s3://bucket/AWSLogs/123456789012/elasticloadbalancing/*/2024/*",
+            "s3://bucket/AWSLogs/123456789012/elasticloadbalancing/us-east-1/2024/{01,02}/",
+            "s3://bucket/AWSLogs/123456789012/elasticloadbalancing/*/2025/*",
+            "s3://other/logs/access.log",
+        ]
+        .map(String::from);
+
+        assert_eq!(
+            scope(&uris),
+            vec![
+                "s3://bucket/AWSLogs/123456789012/elasticloadbalancing/",
+                "s3://bucket/AWSLogs/123456789012/elasticloadbalancing/us-east-1/2024/",
+                "s3://other/logs/access.log",
+            ]
+        );
+    }
+
+    fn secret_for(conn: &Connection, path: &str) -> Option<String> {
+        conn.query_row("SELECT name FROM which_secret(?, 'http')", [path], |row| {
+            row.get(0)
+        })
+        .ok()
+    }
+
+    // HTTP secrets are built in, unlike those of the object stores
+    #[test]
+    fn secrets_are_scoped_to_their_request_and_dropped_with_it() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut secrets = Secrets::new(&conn).unwrap();
+        secrets
+            .create(
+                "http",
+                vec!["TYPE HTTP".to_string()],
+                &["https://example.com/a/*.log".to_string()],
+            )
+            .unwrap();
+        let mut other = Secrets::new(&conn).unwrap();
+        other
+            .create(
+                "http",
+                vec!["TYPE HTTP".to_string()],
+                &["https://example.com/b/".to_string()],
+            )
+            .unwrap();
+
+        let name = secret_for(&conn, "https://example.com/a/1.log").unwrap();
+        assert_eq!(Some(name.clone()), secrets.names.first().cloned());
+        assert_ne!(secret_for(&conn, "https://example.com/b/1.log"), Some(name));
+
+        drop(secrets);
+        assert_eq!(secret_for(&conn, "https://example.com/a/1.log"), None);
+        assert!(secret_for(&conn, "https://example.com/b/1.log").is_some());
+    }
+
+    #[test]
+    fn gcs_options_without_endpoint() {
+        assert_eq!(
+            gcs_options(&credential("")),
+            vec!["TYPE GCS", "KEY_ID 'GOOG1EXAMPLE'", "SECRET 'it''s secret'"]
+        );
+    }
+
+    #[test]
+    fn gcs_options_with_http_endpoint() {
+        assert_eq!(
+            gcs_options(&credential("http://localhost:4443"))[3..],
+            [
+                "ENDPOINT 'localhost:4443'",
+                "URL_STYLE 'path'",
+                "USE_SSL false"
+            ]
+        );
+    }
+
+    #[test]
+    fn gcs_options_with_https_or_bare_endpoint() {
+        for endpoint in ["https://storage.example.com", "storage.example.com"] {
+            assert_eq!(
+                gcs_options(&credential(endpoint))[3..],
+                [
+                    "ENDPOINT 'storage.example.com'",
+                    "URL_STYLE 'path'",
+                    "USE_SSL true"
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use crate::repo::{catalog, sql_string, table, view};
+use crate::util::job::now;
+use duckdb::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const MANIFEST_FILE: &str = "manifest.toml";
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    version: u32,
+    lupe_version: String,
+    created_at: String,
+    tables: Vec<SnapshotTable>,
+    catalog_tables: Vec<SnapshotTable>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotTable {
+    table_name: String,
+    file: String,
+    row_count: u64,
+}
+
+impl Manifest {
+    pub(crate) fn table_names(&self) -> Vec<String> {
+        self.tables
+            .iter()
+            .map(|table| table.table_name.clone())
+            .collect()
+    }
+}
+
+pub(crate) fn exists(dir: &Path) -> bool {
+    dir.join(MANIFEST_FILE).exists()
+}
+
+fn copy_to(conn: &Connection, table_name: &str, dir: &Path) -> duckdb::Result<SnapshotTable> {
+    let file = format!("{}.parquet", table_name);
+    conn.execute_batch(
+        format!(
+            "COPY {} TO {} (FORMAT PARQUET);",
+            table_name,
+            sql_string(&dir.join(&file).to_string_lossy())
+        )
+        .as_str(),
+    )?;
+    let row_count = conn.query_row(
+        format!("SELECT count(*) FROM {};", table_name).as_str(),
+        [],
+        |row| row.get(0),
+    )?;
+
+    Ok(SnapshotTable {
+        table_name: table_name.to_string(),
+        file,
+        row_count,
+    })
+}
+
+// Writes every table and the catalog tables as Parquet files, in one transaction so that
+// they are consistent with each other, and then the manifest listing them.
+pub(crate) fn save(
+    conn: &mut Connection,
+    dir: &Path,
+) -> Result<Manifest, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dir)?;
+
+    let tx = conn.transaction()?;
+    let mut tables = Vec::new();
+    for table_name in table::list(&tx)?.iter() {
+        tables.push(copy_to(&tx, table_name, dir)?);
+    }
+    let mut catalog_tables = Vec::new();
+    for table_name in catalog::INTERNAL_TABLES.iter() {
+        catalog_tables.push(copy_to(&tx, table_name, dir)?);
+    }
+    tx.commit()?;
+
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        lupe_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: now(),
+        tables,
+        catalog_tables,
+    };
+    std::fs::write(dir.join(MANIFEST_FILE), toml::to_string(&manifest)?)?;
+
+    Ok(manifest)
+}
+
+pub(crate) fn read_manifest(dir: &Path) -> Result<Manifest, Box<dyn std::error::Error>> {
+    let path = dir.join(MANIFEST_FILE);
+    let content =
+        std::fs::read_to_string(&path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+    let manifest: Manifest =
+        toml::from_str(&content).map_err(|e| format!("failed to parse {:?}: {}", path, e))?;
+    if manifest.version > MANIFEST_VERSION {
+        return Err(format!(
+            "snapshot version {} is not supported, upgrade lupe (saved by lupe {})",
+            manifest.version, manifest.lupe_version
+        )
+        .into());
+    }
+
+    Ok(manifest)
+}
+
+// Recreates the tables and views of the snapshot and adds their catalog entries. Materialized
+// tables become regular tables.
+pub(crate) fn restore(
+    conn: &mut Connection,
+    dir: &Path,
+    manifest: &Manifest,
+) -> duckdb::Result<()> {
+    let tx = conn.transaction()?;
+    for table in manifest.tables.iter() {
+        tx.execute_batch(
+            format!(
+                "CREATE TABLE {} AS FROM read_parquet({});",
+                table.table_name,
+                sql_string(&dir.join(&table.file).to_string_lossy())
+            )
+            .as_str(),
+        )?;
+    }
+    for table in manifest.catalog_tables.iter() {
+        let source = format!(
+            "read_parquet({})",
+            sql_string(&dir.join(&table.file).to_string_lossy())
+        );
+        tx.execute_batch(
+            format!("INSERT INTO {} BY NAME FROM {};", table.table_name, source).as_str(),
+        )?;
+        if table.table_name == catalog::VIEWS_TABLE {
+            view::recreate(&tx, &source)?;
+        }
+    }
+    for table in manifest.tables.iter() {
+        catalog::clear_location(&tx, &table.table_name)?;
+    }
+
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::catalog::CatalogEntry;
+    use crate::repo::tests::connection;
+    use crate::repo::view::ViewEntry;
+    use std::time::Duration;
+
+    #[test]
+    fn saved_snapshot_is_restored_with_catalog_and_views() {
+        let dir = std::env::temp_dir().join(format!("lupe-snapshot-test-{}", std::process::id()));
+        let mut conn = connection();
+        conn.execute_batch(
+            "CREATE TABLE alb_logs AS SELECT * FROM (VALUES ('a'), ('b')) AS logs(elb);
+            CREATE TABLE alb_logs_rejects (line VARCHAR);",
+        )
+        .unwrap();
+        let entry = CatalogEntry::builder()
+            .table_name("alb_logs".to_string())
+            .table_type("alb".to_string())
+            .uris(vec!["s3://bucket/AWSLogs/".to_string()])
+            .row_count(2)
+            .byte_count(100)
+            .duration(Duration::from_secs(1))
+            .sample(Some("10% of objects".to_string()))
+            .build();
+        catalog::insert(&conn, &entry).unwrap();
+        let view = ViewEntry::builder()
+            .view_name("elbs".to_string())
+            .sql("SELECT DISTINCT elb FROM alb_logs;".to_string())
+            .build();
+        view::create(&mut conn, &view, false).unwrap();
+
+        let saved = save(&mut conn, &dir).unwrap();
+        let mut restored = connection();
+        let result = read_manifest(&dir).and_then(|manifest| {
+            restore(&mut restored, &dir, &manifest)?;
+            Ok(manifest)
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+        let manifest = result.unwrap();
+
+        assert_eq!(manifest.table_names(), saved.table_names());
+        assert_eq!(
+            table::list(&restored).unwrap(),
+            ["alb_logs", "alb_logs_rejects"]
+        );
+        let restored_entries = catalog::list(&restored).unwrap();
+        assert_eq!(
+            format!("{:?}", restored_entries),
+            format!("{:?}", catalog::list(&conn).unwrap())
+        );
+        let restored_views = view::list(&restored).unwrap();
+        assert_eq!(
+            format!("{:?}", restored_views),
+            format!("{:?}", view::list(&conn).unwrap())
+        );
+        let elb_count: usize = restored
+            .query_row("SELECT count(*) FROM elbs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(elb_count, 2);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::pb::db::ColumnInfo;
+use crate::repo::{catalog, rejects_table_name, sql_list, view};
+use duckdb::{Connection, Transaction};
+use typed_builder::TypedBuilder;
+
+#[derive(TypedBuilder)]
+pub(crate) struct ColumnSummary {
+    column_name: String,
+    column_type: String,
+    null_percentage: String,
+    min: Option<String>,
+    max: Option<String>,
+    #[builder(default)]
+    description: Option<&'static str>,
+}
+
+impl ColumnSummary {
+    pub(crate) fn describe(&mut self, descriptions: &[(&str, &'static str)]) {
+        self.description = descriptions
+            .iter()
+            .find(|(name, _)| *name == self.column_name)
+            .map(|(_, description)| *description);
+    }
+}
+
+pub(crate) fn summarize(conn: &Connection, table_name: &str) -> duckdb::Result<Vec<ColumnSummary>> {
+    let mut stmt = conn.prepare(
+        format!(
+            "SELECT column_name, column_type, CAST(null_percentage AS VARCHAR), min, max
+            FROM (SUMMARIZE {})",
+            table_name
+        )
+        .as_str(),
+    )?;
+
+    let columns = stmt.query_map([], |row| {
+        Ok(ColumnSummary::builder()
+            .column_name(row.get(0)?)
+            .column_type(row.get(1)?)
+            .null_percentage(row.get(2)?)
+            .min(row.get(3)?)
+            .max(row.get(4)?)
+            .build())
+    })?;
+
+    columns.collect()
+}
+
+impl From<ColumnSummary> for ColumnInfo {
+    fn from(value: ColumnSummary) -> Self {
+        Self {
+            column_name: value.column_name,
+            column_type: value.column_type,
+            null_percentage: value.null_percentage,
+            min: value.min.unwrap_or_default(),
+            max: value.max.unwrap_or_default(),
+            description: value.description.unwrap_or_default().to_string(),
+        }
+    }
+}
+
+// Materialized tables are included although they are views.
+pub(crate) fn list(conn: &Connection) -> duckdb::Result<Vec<String>> {
+    let internal_tables = catalog::INTERNAL_TABLES.map(|name| name.to_string());
+    let mut stmt = conn.prepare(
+        format!(
+            "SELECT table_name FROM duckdb_tables()
+            WHERE NOT list_contains({}, table_name)
+            UNION
+            SELECT table_name FROM {} WHERE location IS NOT NULL
+            ORDER BY table_name",
+            sql_list(&internal_tables),
+            catalog::CATALOG_TABLE
+        )
+        .as_str(),
+    )?;
+    let table_names = stmt.query_map([], |row| row.get(0))?;
+
+    table_names.collect()
+}
+
+pub(crate) fn drop_tables(conn: &mut Connection, table_names: &[String]) -> duckdb::Result<()> {
+    let tx = conn.transaction()?;
+    drop_in(&tx, table_names)?;
+
+    tx.commit()
+}
+
+// Returns the lupe views which could no longer be queried once the tables are dropped.
+pub(crate) fn dependent_views(
+    conn: &mut Connection,
+    table_names: &[String],
+) -> duckdb::Result<Vec<String>> {
+    let tx = conn.transaction()?;
+    drop_in(&tx, table_names)?;
+    let view_names = view::list(&tx)?
+        .into_iter()
+        .map(|entry| entry.view_name().to_string())
+        .filter(|view_name| {
+            tx.execute_batch(format!("SELECT * FROM {} LIMIT 0;", view_name).as_str())
+                .is_err()
+        })
+        .collect();
+
+    tx.rollback()?;
+    Ok(view_names)
+}
+
+fn drop_in(tx: &Transaction, table_names: &[String]) -> duckdb::Result<()> {
+    for table_name in table_names.iter() {
+        // rejects tables are dropped with their tables, e.g. when dropping all the tables
+        if table_names
+            .iter()
+            .any(|name| rejects_table_name(name) == *table_name)
+        {
+            continue;
+        }
+
+        // materialized tables are views, and their Parquet files are kept
+        let kind = match catalog::location(tx, table_name)? {
+            Some(_) => "VIEW",
+            None => "TABLE",
+        };
+        tx.execute_batch(
+            format!(
+                "DROP {} {};
+                DROP TABLE IF EXISTS {};",
+                kind,
+                table_name,
+                rejects_table_name(table_name)
+            )
+            .as_str(),
+        )?;
+        catalog::remove(tx, table_name)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::tests::connection;
+    use crate::repo::view::ViewEntry;
+
+    #[test]
+    fn drop_all_tables_with_rejects() {
+        let mut conn = connection();
+        conn.execute_batch(
+            "CREATE TABLE alb_logs (line VARCHAR);
+            CREATE TABLE alb_logs_rejects (line VARCHAR);
+            CREATE TABLE s3_logs (line VARCHAR);",
+        )
+        .unwrap();
+
+        let table_names = list(&conn).unwrap();
+        assert_eq!(table_names, ["alb_logs", "alb_logs_rejects", "s3_logs"]);
+
+        drop_tables(&mut conn, &table_names).unwrap();
+        assert!(list(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn views_of_dropped_tables_are_dependent() {
+        let mut conn = connection();
+        conn.execute_batch(
+            "CREATE TABLE alb_logs (elb VARCHAR);
+            CREATE TABLE s3_logs (bucket VARCHAR);
+            CREATE TABLE s3_logs_rejects (line VARCHAR);",
+        )
+        .unwrap();
+        for (view_name, sql) in [
+            ("elbs", "SELECT DISTINCT elb FROM alb_logs"),
+            ("elb_count", "SELECT count(*) FROM elbs"),
+            ("buckets", "SELECT DISTINCT bucket FROM s3_logs"),
+        ] {
+            let entry = ViewEntry::builder()
+                .view_name(view_name.to_string())
+                .sql(sql.to_string())
+                .build();
+            view::create(&mut conn, &entry, false).unwrap();
+        }
+
+        let table_names = ["alb_logs".to_string()];
+        assert_eq!(
+            dependent_views(&mut conn, &table_names).unwrap(),
+            ["elb_count", "elbs"]
+        );
+        // nothing is dropped while looking for the views
+        assert_eq!(
+            list(&conn).unwrap(),
+            ["alb_logs", "s3_logs", "s3_logs_rejects"]
+        );
+        assert_eq!(view::list(&conn).unwrap().len(), 3);
+        assert!(dependent_views(&mut conn, &["s3_logs_rejects".to_string()])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn drop_rejects_table_alone() {
+        let mut conn = connection();
+        conn.execute_batch(
+            "CREATE TABLE alb_logs (line VARCHAR);
+            CREATE TABLE alb_logs_rejects (line VARCHAR);",
+        )
+        .unwrap();
+
+        drop_tables(&mut conn, &["alb_logs_rejects".to_string()]).unwrap();
+        assert_eq!(list(&conn).unwrap(), ["alb_logs"]);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::pb::db::ViewInfo;
+use crate::repo::catalog::VIEWS_TABLE;
+use chrono::Utc;
+use duckdb::{params, Connection};
+use typed_builder::TypedBuilder;
+
+#[derive(Debug, TypedBuilder)]
+pub(crate) struct ViewEntry {
+    view_name: String,
+    sql: String,
+    #[builder(default = Utc::now().naive_utc().to_string())]
+    created_at: String,
+}
+
+impl ViewEntry {
+    pub(crate) fn view_name(&self) -> &str {
+        &self.view_name
+    }
+}
+
+fn create_statement(view_name: &str, sql: &str, replace: bool) -> String {
+    format!(
+        "CREATE {}VIEW {} AS {}",
+        if replace { "OR REPLACE " } else { "" },
+        view_name,
+        sql.trim().trim_end_matches(';')
+    )
+}
+
+// The view is recorded with its query as given, so that `lupe view ls` shows it as written
+// rather than as DuckDB normalizes it.
+pub(crate) fn create(
+    conn: &mut Connection,
+    entry: &ViewEntry,
+    replace: bool,
+) -> duckdb::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        create_statement(&entry.view_name, &entry.sql, replace).as_str(),
+        [],
+    )?;
+    tx.execute(
+        format!("DELETE FROM {} WHERE view_name = ?", VIEWS_TABLE).as_str(),
+        params![entry.view_name],
+    )?;
+    tx.execute(
+        format!(
+            "INSERT INTO {} VALUES (?, ?, CAST(? AS TIMESTAMP))",
+            VIEWS_TABLE
+        )
+        .as_str(),
+        params![entry.view_name, entry.sql, entry.created_at],
+    )?;
+
+    tx.commit()
+}
+
+pub(crate) fn list(conn: &Connection) -> duckdb::Result<Vec<ViewEntry>> {
+    let mut stmt = conn.prepare(
+        format!(
+            "SELECT view_name, sql, CAST(created_at AS VARCHAR) FROM {} ORDER BY view_name",
+            VIEWS_TABLE
+        )
+        .as_str(),
+    )?;
+
+    let entries = stmt.query_map([], |row| {
+        Ok(ViewEntry::builder()
+            .view_name(row.get(0)?)
+            .sql(row.get(1)?)
+            .created_at(row.get(2)?)
+            .build())
+    })?;
+
+    entries.collect()
+}
+
+// Creates the views recorded in `source`, e.g. the lupe_views table of a snapshot.
+pub(crate) fn recreate(conn: &Connection, source: &str) -> duckdb::Result<()> {
+    let mut stmt = conn.prepare(format!("SELECT view_name, sql FROM {}", source).as_str())?;
+    let views = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<duckdb::Result<Vec<(String, String)>>>()?;
+
+    for (view_name, sql) in views.iter() {
+        conn.execute(create_statement(view_name, sql, false).as_str(), [])?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn drop_views(conn: &mut Connection, view_names: &[String]) -> duckdb::Result<()> {
+    let tx = conn.transaction()?;
+    for view_name in view_names.iter() {
+        tx.execute_batch(format!("DROP VIEW {};", view_name).as_str())?;
+        tx.execute(
+            format!("DELETE FROM {} WHERE view_name = ?", VIEWS_TABLE).as_str(),
+            params![view_name],
+        )?;
+    }
+
+    tx.commit()
+}
+
+impl From<ViewEntry> for ViewInfo {
+    fn from(value: ViewEntry) -> Self {
+        Self {
+            view_name: value.view_name,
+            sql: value.sql,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::tests::connection;
+
+    fn entry(view_name: &str, sql: &str) -> ViewEntry {
+        ViewEntry::builder()
+            .view_name(view_name.to_string())
+            .sql(sql.to_string())
+            .build()
+    }
+
+    fn sqls(conn: &Connection) -> Vec<String> {
+        list(conn)
+            .unwrap()
+            .into_iter()
+            .map(|entry| format!("{}: {}", entry.view_name, entry.sql))
+            .collect()
+    }
+
+    #[test]
+    fn views_are_recorded_as_written_until_dropped() {
+        let mut conn = connection();
+        conn.execute_batch("CREATE TABLE alb_logs AS SELECT 'a' AS elb;")
+            .unwrap();
+
+        create(
+            &mut conn,
+            &entry("elbs", "select elb from alb_logs;"),
+            false,
+        )
+        .unwrap();
+        assert!(create(&mut conn, &entry("elbs", "SELECT 1"), false).is_err());
+        assert!(create(&mut conn, &entry("broken", "SELECT * FROM missing"), false).is_err());
+        assert_eq!(sqls(&conn), ["elbs: select elb from alb_logs;"]);
+
+        create(
+            &mut conn,
+            &entry("elbs", "SELECT DISTINCT elb FROM alb_logs"),
+            true,
+        )
+        .unwrap();
+        assert_eq!(sqls(&conn), ["elbs: SELECT DISTINCT elb FROM alb_logs"]);
+        let elb: String = conn
+            .query_row("SELECT elb FROM elbs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(elb, "a");
+
+        drop_views(&mut conn, &["elbs".to_string()]).unwrap();
+        assert!(sqls(&conn).is_empty());
+        assert!(conn.execute_batch("SELECT * FROM elbs").is_err());
+    }
+}
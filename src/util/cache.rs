@@ -0,0 +1,318 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use typed_builder::TypedBuilder;
+
+const S3_SCHEME: &str = "s3://";
+const PART_EXTENSION: &str = "part";
+const SOURCE_EXTENSION: &str = "toml";
+
+pub(crate) fn get_cache_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")),
+    }
+    .map(|dir| dir.join("lupe").join("objects"))
+}
+
+// the object a cached file is a copy of
+#[derive(Serialize, Deserialize)]
+struct Source {
+    uri: String,
+    size: u64,
+    last_modified_ms: u64,
+}
+
+impl Source {
+    fn new(uri: &str, size: u64, last_modified: SystemTime) -> Self {
+        Self {
+            uri: uri.to_string(),
+            size,
+            last_modified_ms: last_modified
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        }
+    }
+
+    // Cached files are named after a hash of the version of the object they are a copy of.
+    fn path(&self, cache_dir: &Path) -> PathBuf {
+        let hash = Sha256::digest(format!(
+            "{}\n{}\n{}",
+            self.uri, self.size, self.last_modified_ms
+        ))
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+        let extension = if self.uri.ends_with(".gz") {
+            "log.gz"
+        } else {
+            "log"
+        };
+
+        cache_dir.join(format!("{}.{}", hash, extension))
+    }
+}
+
+// <hash>.log.gz -> <hash>.<extension>
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let hash = name.split('.').next().unwrap_or_default();
+
+    path.with_file_name(format!("{}.{}", hash, extension))
+}
+
+// Returns the path of the cached copy of a version of an s3 object, whether it is cached
+// or not.
+pub(crate) fn object_path(uri: &str, size: u64, last_modified: SystemTime) -> Option<PathBuf> {
+    if !uri.starts_with(S3_SCHEME) {
+        return None;
+    }
+
+    Some(Source::new(uri, size, last_modified).path(&get_cache_dir()?))
+}
+
+pub(crate) fn is_cached(path: &Path) -> bool {
+    path.is_file()
+}
+
+pub(crate) fn store(
+    path: &Path,
+    content: &[u8],
+    uri: &str,
+    last_modified: SystemTime,
+) -> Result<(), std::io::Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let source = Source::new(uri, content.len() as u64, last_modified);
+    std::fs::write(
+        sibling_path(path, SOURCE_EXTENSION),
+        toml::to_string(&source).map_err(std::io::Error::other)?,
+    )?;
+
+    let part_path = sibling_path(path, PART_EXTENSION);
+    let mut file = std::fs::File::create(&part_path)?;
+    file.write_all(content)?;
+    std::fs::rename(part_path, path)
+}
+
+#[derive(TypedBuilder)]
+pub(crate) struct CacheEntry {
+    pub(crate) uri: String,
+    pub(crate) path: PathBuf,
+    pub(crate) size: u64,
+    // of the source object
+    pub(crate) last_modified: SystemTime,
+    pub(crate) fetched_at: SystemTime,
+}
+
+pub(crate) fn list() -> Result<Vec<CacheEntry>, std::io::Error> {
+    match get_cache_dir() {
+        Some(cache_dir) => list_in(&cache_dir),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn list_in(cache_dir: &Path) -> Result<Vec<CacheEntry>, std::io::Error> {
+    let mut entries = Vec::new();
+    for (_, source) in sources(cache_dir)? {
+        let Some(source) = source else {
+            continue;
+        };
+        let path = source.path(cache_dir);
+        let Ok(metadata) = path.metadata() else {
+            continue;
+        };
+
+        entries.push(
+            CacheEntry::builder()
+                .uri(source.uri)
+                .path(path)
+                .size(metadata.len())
+                .last_modified(UNIX_EPOCH + Duration::from_millis(source.last_modified_ms))
+                .fetched_at(metadata.modified()?)
+                .build(),
+        );
+    }
+    entries.sort_by(|a, b| a.uri.cmp(&b.uri));
+
+    Ok(entries)
+}
+
+// source files of the cache, with their contents unless they cannot be read
+fn sources(cache_dir: &Path) -> Result<Vec<(PathBuf, Option<Source>)>, std::io::Error> {
+    if !cache_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut sources = Vec::new();
+    for entry in std::fs::read_dir(cache_dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == SOURCE_EXTENSION)
+        {
+            let source = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| toml::from_str(&content).ok());
+            sources.push((path, source));
+        }
+    }
+
+    Ok(sources)
+}
+
+// Objects are pruned by when they were fetched, regardless of how old the source objects are.
+pub(crate) fn prune(older_than: Option<Duration>) -> Result<Vec<CacheEntry>, std::io::Error> {
+    match get_cache_dir() {
+        Some(cache_dir) => prune_in(&cache_dir, older_than),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn prune_in(
+    cache_dir: &Path,
+    older_than: Option<Duration>,
+) -> Result<Vec<CacheEntry>, std::io::Error> {
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+    for entry in list_in(cache_dir)? {
+        let expired = match older_than {
+            Some(older_than) => now
+                .duration_since(entry.fetched_at)
+                .is_ok_and(|age| age > older_than),
+            None => true,
+        };
+        if expired {
+            std::fs::remove_file(&entry.path)?;
+            std::fs::remove_file(sibling_path(&entry.path, SOURCE_EXTENSION))?;
+            removed.push(entry);
+        }
+    }
+
+    // source files left behind by fetches which failed
+    for (source_path, source) in sources(cache_dir)? {
+        if source.is_none_or(|source| !source.path(cache_dir).exists()) {
+            std::fs::remove_file(source_path)?;
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CacheDir(PathBuf);
+
+    impl CacheDir {
+        fn create(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!(
+                "lupe-cache-test-{}-{}",
+                std::process::id(),
+                name
+            )))
+        }
+
+        fn store(&self, uri: &str, content: &[u8], last_modified: SystemTime) -> PathBuf {
+            let path = Source::new(uri, content.len() as u64, last_modified).path(&self.0);
+            store(&path, content, uri, last_modified).unwrap();
+
+            path
+        }
+
+        fn uris(&self) -> Vec<String> {
+            list_in(&self.0)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.uri)
+                .collect()
+        }
+    }
+
+    impl Drop for CacheDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn last_modified() -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)
+    }
+
+    #[test]
+    fn objects_are_cached_per_version() {
+        let cache_dir = CacheDir::create("versions");
+        let path = cache_dir.store("s3://bucket/key.log.gz", b"line\n", last_modified());
+        let path_of = |size: u64, last_modified: SystemTime| {
+            Source::new("s3://bucket/key.log.gz", size, last_modified).path(&cache_dir.0)
+        };
+
+        assert!(is_cached(&path));
+        assert_eq!(path_of(5, last_modified()), path);
+        assert!(!is_cached(&path_of(6, last_modified())));
+        assert!(!is_cached(&path_of(
+            5,
+            last_modified() + Duration::from_secs(1)
+        )));
+        assert!(path.to_string_lossy().ends_with(".log.gz"));
+
+        let entries = list_in(&cache_dir.0).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].last_modified, last_modified());
+        // cached files are as old as the fetch, not as the source object
+        assert!(entries[0].fetched_at > last_modified());
+    }
+
+    #[test]
+    fn keys_named_like_cache_files_are_cached_apart() {
+        let cache_dir = CacheDir::create("suffixes");
+        let uris = [
+            "s3://bucket/key",
+            "s3://bucket/key.part",
+            "s3://bucket/key.source",
+            "s3://bucket/key.toml",
+        ];
+        for uri in uris {
+            cache_dir.store(uri, uri.as_bytes(), last_modified());
+        }
+
+        assert_eq!(cache_dir.uris(), uris);
+        for entry in list_in(&cache_dir.0).unwrap() {
+            assert_eq!(std::fs::read(entry.path).unwrap(), entry.uri.as_bytes());
+        }
+    }
+
+    #[test]
+    fn keys_prefixing_other_keys_are_cached_apart() {
+        let cache_dir = CacheDir::create("prefixes");
+        let uris = ["s3://bucket/a/b", "s3://bucket/a/b/c"];
+        cache_dir.store(uris[1], b"c\n", last_modified());
+        cache_dir.store(uris[0], b"b\n", last_modified());
+
+        assert_eq!(cache_dir.uris(), uris);
+    }
+
+    #[test]
+    fn pruned_objects_are_removed_with_their_sources() {
+        let cache_dir = CacheDir::create("prune");
+        cache_dir.store("s3://bucket/kept", b"line\n", last_modified());
+        let removed = cache_dir.store("s3://bucket/removed", b"line\n", last_modified());
+        // a fetch which failed after writing the source file
+        std::fs::remove_file(&removed).unwrap();
+        cache_dir.store("s3://bucket/pruned", b"line\n", last_modified());
+
+        let pruned = prune_in(&cache_dir.0, Some(Duration::from_secs(3600))).unwrap();
+        assert!(pruned.is_empty());
+        assert_eq!(cache_dir.uris(), ["s3://bucket/kept", "s3://bucket/pruned"]);
+
+        let pruned = prune_in(&cache_dir.0, None).unwrap();
+        assert_eq!(pruned.len(), 2);
+        assert_eq!(std::fs::read_dir(&cache_dir.0).unwrap().count(), 0);
+    }
+}
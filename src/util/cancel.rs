@@ -0,0 +1,144 @@
+use duckdb::{Connection, InterruptHandle};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinError;
+
+// a statement may start right after an interrupt, so it is interrupted again until the work
+// on the connection has finished
+const INTERRUPT_INTERVAL: Duration = Duration::from_millis(100);
+
+// Cancels the work of a request on its connection by interrupting the running statement,
+// e.g. once the client has gone away or the task of its job is aborted.
+#[derive(Clone)]
+pub(crate) struct Cancellation {
+    handle: Arc<InterruptHandle>,
+    cancelled: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    finished_notify: Arc<Notify>,
+}
+
+// marks the work finished even if it panics
+struct Finished(Cancellation);
+
+impl Drop for Finished {
+    fn drop(&mut self) {
+        self.0.finished.store(true, Ordering::SeqCst);
+        self.0.finished_notify.notify_one();
+    }
+}
+
+// cancels the work unless it has finished
+struct CancelOnDrop(Cancellation);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+impl Cancellation {
+    pub(crate) fn new(conn: &Connection) -> Self {
+        Self {
+            handle: conn.interrupt_handle(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            finished: Arc::new(AtomicBool::new(false)),
+            finished_notify: Arc::new(Notify::new()),
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn cancel(&self) {
+        if self.finished.load(Ordering::SeqCst) || self.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let cancellation = self.clone();
+        tokio::spawn(async move {
+            while !cancellation.finished.load(Ordering::SeqCst) {
+                cancellation.handle.interrupt();
+                tokio::time::sleep(INTERRUPT_INTERVAL).await;
+            }
+        });
+    }
+
+    // Cancels once `cancelled` completes, unless the work has finished before.
+    pub(crate) fn cancel_on<F>(&self, cancelled: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let cancellation = self.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = cancelled => cancellation.cancel(),
+                _ = cancellation.finished_notify.notified() => {}
+            }
+        });
+    }
+
+    // Runs `f` on the blocking pool, and cancels it if the returned future is dropped
+    // before it has finished.
+    pub(crate) async fn run_blocking<T, F>(&self, f: F) -> Result<T, JoinError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let _cancel_on_drop = CancelOnDrop(self.clone());
+        let finished = Finished(self.clone());
+        tokio::task::spawn_blocking(move || {
+            let _finished = finished;
+            f()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    const LONG_QUERY: &str = "SELECT count(*) FROM range(100000000000) WHERE range % 7 = 3";
+
+    #[tokio::test]
+    async fn dropped_request_interrupts_running_statement() {
+        let conn = Connection::open_in_memory().unwrap();
+        let cancellation = Cancellation::new(&conn);
+        let (tx, rx) = mpsc::channel();
+        let running = cancellation.run_blocking(move || {
+            let _ = tx.send(conn.query_row(LONG_QUERY, [], |row| row.get::<_, i64>(0)));
+        });
+
+        assert!(tokio::time::timeout(Duration::from_millis(200), running)
+            .await
+            .is_err());
+        let result = tokio::task::spawn_blocking(move || rx.recv_timeout(Duration::from_secs(10)))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(cancellation.is_cancelled());
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn finished_work_is_not_cancelled() {
+        let conn = Connection::open_in_memory().unwrap();
+        let cancellation = Cancellation::new(&conn);
+        cancellation.cancel_on(std::future::pending());
+
+        let count = cancellation
+            .run_blocking(move || conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0)))
+            .await
+            .unwrap();
+        cancellation.cancel();
+
+        assert_eq!(count.unwrap(), 1);
+        assert!(!cancellation.is_cancelled());
+    }
+}
@@ -0,0 +1,68 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::debug;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct Config {
+    pub(crate) load: LoadConfig,
+    pub(crate) cache: CacheConfig,
+    pub(crate) s3: S3Config,
+    pub(crate) server: ServerConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct LoadConfig {
+    pub(crate) max_objects: u64,
+    pub(crate) max_bytes: u64,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            max_objects: 50_000,
+            max_bytes: 5 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct CacheConfig {
+    pub(crate) enabled: bool,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct S3Config {
+    pub(crate) requester_pays: bool,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct ServerConfig {
+    pub(crate) db_path: Option<PathBuf>,
+}
+
+pub(crate) fn get_config_path() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    }
+    .map(|dir| dir.join("lupe").join("config.toml"))
+}
+
+pub(crate) fn load() -> Result<Config, Box<dyn std::error::Error>> {
+    let Some(path) = get_config_path() else {
+        return Ok(Config::default());
+    };
+    if !path.exists() {
+        debug!("config file not found: {:?}", path);
+        return Ok(Config::default());
+    }
+
+    debug!("loading config from {:?}", path);
+    let content = std::fs::read_to_string(&path)?;
+    toml::from_str(&content).map_err(|e| format!("failed to parse {:?}: {}", path, e).into())
+}
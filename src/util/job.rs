@@ -0,0 +1,169 @@
+use crate::pb::db::{JobInfo, LoadProgress};
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use typed_builder::TypedBuilder;
+
+pub(crate) fn now() -> String {
+    Utc::now()
+        .naive_utc()
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct JobStatus {
+    runs: u64,
+    row_count: u64,
+    last_run: Option<String>,
+    last_error: Option<String>,
+    progress: Option<LoadProgress>,
+    // jobs which run once, such as detached loads, finish after their run
+    finished: bool,
+    cancelled: bool,
+}
+
+impl JobStatus {
+    pub(crate) fn progressed(&mut self, progress: LoadProgress) {
+        self.progress = Some(progress);
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn state(&self) -> &'static str {
+        match (self.finished, self.last_error.is_some()) {
+            (false, _) => "running",
+            (true, false) => "succeeded",
+            (true, true) => "failed",
+        }
+    }
+
+    pub(crate) fn succeeded(&mut self, row_count: u64) {
+        self.runs += 1;
+        self.row_count += row_count;
+        self.last_run = Some(now());
+        self.last_error = None;
+    }
+
+    pub(crate) fn failed(&mut self, error: String) {
+        self.runs += 1;
+        self.last_run = Some(now());
+        self.last_error = Some(error);
+    }
+}
+
+#[derive(Debug, TypedBuilder)]
+pub(crate) struct JobSpec {
+    kind: &'static str,
+    table_name: String,
+    uris: Vec<String>,
+    #[builder(default)]
+    interval: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Job {
+    spec: JobSpec,
+    started_at: String,
+    status: Arc<Mutex<JobStatus>>,
+    handle: JoinHandle<()>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Jobs {
+    next_id: AtomicU64,
+    jobs: Mutex<BTreeMap<u64, Job>>,
+}
+
+impl Jobs {
+    pub(crate) async fn spawn<F, Fut>(&self, spec: JobSpec, task: F) -> u64
+    where
+        F: FnOnce(Arc<Mutex<JobStatus>>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let status = Arc::new(Mutex::new(JobStatus::default()));
+        let handle = tokio::spawn(task(status.clone()));
+
+        self.jobs.lock().await.insert(
+            id,
+            Job {
+                spec,
+                started_at: now(),
+                status,
+                handle,
+            },
+        );
+
+        id
+    }
+
+    pub(crate) async fn list(&self) -> Vec<JobInfo> {
+        let jobs = self.jobs.lock().await;
+
+        let mut infos = Vec::new();
+        for (id, job) in jobs.iter() {
+            let status = job.status.lock().await;
+            infos.push(JobInfo {
+                job_id: *id,
+                kind: job.spec.kind.to_string(),
+                table_name: job.spec.table_name.clone(),
+                uris: job.spec.uris.clone(),
+                interval_secs: job.spec.interval.unwrap_or_default().as_secs(),
+                started_at: job.started_at.clone(),
+                runs: status.runs,
+                row_count: status.row_count,
+                last_run: status.last_run.clone().unwrap_or_default(),
+                last_error: status.last_error.clone().unwrap_or_default(),
+                state: status.state().to_string(),
+                progress: status.progress,
+            });
+        }
+
+        infos
+    }
+
+    pub(crate) async fn get(&self, id: u64) -> Option<JobInfo> {
+        self.list().await.into_iter().find(|info| info.job_id == id)
+    }
+
+    pub(crate) async fn cancel(&self, id: u64) -> bool {
+        match self.jobs.lock().await.remove(&id) {
+            Some(job) => {
+                job.status.lock().await.cancelled = true;
+                job.handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) async fn cancel_table(&self, table_name: &str) -> Vec<u64> {
+        let mut jobs = self.jobs.lock().await;
+
+        let ids = jobs
+            .iter()
+            .filter(|(_, job)| job.spec.table_name == table_name)
+            .map(|(id, _)| *id)
+            .collect::<Vec<u64>>();
+        for id in ids.iter() {
+            if let Some(job) = jobs.remove(id) {
+                job.status.lock().await.cancelled = true;
+                job.handle.abort();
+            }
+        }
+
+        ids
+    }
+}
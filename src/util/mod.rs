@@ -1 +1,7 @@
+pub(crate) mod cache;
+pub(crate) mod cancel;
+pub(crate) mod config;
+pub(crate) mod format;
+pub(crate) mod job;
 pub(crate) mod uds;
+pub(crate) mod upload;
@@ -0,0 +1,49 @@
+use std::collections::hash_map::RandomState;
+use std::fs::File;
+use std::hash::BuildHasher;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Temporary file holding logs streamed from the client, removed on drop.
+pub(crate) struct StreamUpload {
+    path: PathBuf,
+}
+
+impl StreamUpload {
+    pub(crate) fn create(first_chunk: &[u8]) -> std::io::Result<(Self, File)> {
+        let extension = if first_chunk.starts_with(&GZIP_MAGIC) {
+            "log.gz"
+        } else {
+            "log"
+        };
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let path = std::env::temp_dir().join(format!(
+            "lupe-upload-{}-{:016x}.{}",
+            std::process::id(),
+            RandomState::new().hash_one(nanos),
+            extension
+        ));
+
+        let file = File::create_new(&path)?;
+
+        Ok((Self { path }, file))
+    }
+
+    pub(crate) fn path_string(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+}
+
+impl Drop for StreamUpload {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("failed to remove {:?}: {}", self.path, e);
+        }
+    }
+}